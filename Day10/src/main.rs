@@ -1,19 +1,100 @@
 use grid::*;
 use std::collections::{HashMap, HashSet, VecDeque};
 fn main() {
-    let input_file = "input.txt";
-    let grid_str = std::fs::read_to_string(input_file).unwrap();
-    let (grid, summits, trail_heads) = load_grid_from_str(&grid_str);
-    let total_summits_reached = find_summits(&grid, &summits, &trail_heads);
-    println!("Part1: {:?}", total_summits_reached);
-    let total_trails = find_trails(&grid, &summits, &trail_heads);
-    println!("Part2: {:?}", total_trails);
+    let args: Vec<String> = std::env::args().collect();
+    let input_file = args.iter().skip(1).find(|arg| !arg.starts_with("--")).map(String::as_str).unwrap_or("input.txt");
+    // --format=whitespace|pgm picks a multi-digit loader, otherwise the puzzle's digit map
+    let format = args.iter().find_map(|arg| arg.strip_prefix("--format=")).unwrap_or("digits");
+    let grid = match format {
+        "whitespace" => load_whitespace_heights(&std::fs::read_to_string(input_file).unwrap()).expect("Bad heightmap"),
+        "pgm" => load_pgm(&std::fs::read(input_file).unwrap()).expect("Bad PGM image"),
+        _ => load_grid_from_str(&std::fs::read_to_string(input_file).unwrap()).0,
+    };
+    // --rule=min,max,start,end replaces the +1 climb from 0 to 9
+    let rule = match args.iter().find_map(|arg| arg.strip_prefix("--rule=")) {
+        Some(rule) => rule.parse().expect("Bad rule"),
+        None => TrailRule::default(),
+    };
+    let summits = cells_at_height(&grid, rule.end);
+    let trail_heads = cells_at_height(&grid, rule.start);
+    if rule == TrailRule::default() {
+        let total_summits_reached = find_summits(&grid, &summits, &trail_heads);
+        println!("Part1: {:?}", total_summits_reached);
+        let total_trails = find_trails(&grid, &summits, &trail_heads);
+        println!("Part2: {:?}", total_trails);
+    } else {
+        let reachable = trail_head_summits(&grid, &rule, &trail_heads);
+        println!("Summits reached: {}", reachable.iter().map(|(_, summits)| summits.len()).sum::<usize>());
+        // level steps or descents can make the trails too many to count; --routes still lists them
+        if rule.min_climb > 0 {
+            println!("Trails: {}", trail_heads.iter().map(|&trail_head| trails_from(&grid, &rule, trail_head).count()).sum::<usize>());
+        }
+    }
+    if args.iter().any(|arg| arg == "--routes") {
+        for (trail_head, reachable) in trail_head_summits(&grid, &rule, &trail_heads) {
+            println!("{:?}: {} summits, {} trails", trail_head, reachable.len(), trails_from(&grid, &rule, trail_head).count());
+        }
+    }
+    // --show=row,col[,n] draws the nth trail (default the first) from that trail head
+    if let Some(target) = args.iter().find_map(|arg| arg.strip_prefix("--show=")) {
+        let numbers: Vec<usize> = target.split(',').map(|n| n.parse().expect("Bad number")).collect();
+        let trail = trails_from(&grid, &rule, (numbers[0], numbers[1]))
+            .nth(numbers.get(2).copied().unwrap_or(0))
+            .expect("No such trail");
+        print!("{}", render_trail(&grid, &trail, true));
+    }
 }
 
-type Summits = HashSet<(usize, usize)>;
-type TrailHeads = HashSet<(usize, usize)>;
+type Position = (usize, usize);
+type Summits = HashSet<Position>;
+type TrailHeads = HashSet<Position>;
+type Trail = Vec<Position>;
+type Height = u32;
+// None marks an impassable cell
+type Heightmap = Grid<Option<Height>>;
 
-fn load_grid_from_str(input: &str) -> (Grid<u8>, Summits, TrailHeads) {
+// Which steps a trail may take and where it starts and ends
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct TrailRule {
+    // inclusive range for the change in height on each step; a negative minimum allows descents
+    min_climb: i64,
+    max_climb: i64,
+    start: Height,
+    end: Height,
+}
+
+impl Default for TrailRule {
+    fn default() -> Self {
+        TrailRule { min_climb: 1, max_climb: 1, start: 0, end: 9 }
+    }
+}
+
+impl TrailRule {
+    fn allows(&self, from: Height, to: Height) -> bool {
+        (self.min_climb..=self.max_climb).contains(&(to as i64 - from as i64))
+    }
+}
+
+// "min,max,start,end", e.g. "-1,2,0,100"
+impl std::str::FromStr for TrailRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(',').map(str::trim).collect();
+        let [min, max, start, end] = parts[..] else {
+            return Err(format!("Expected min,max,start,end but got '{}'", s));
+        };
+        let number = |part: &str| part.parse::<i64>().map_err(|e| format!("Bad number '{}': {}", part, e));
+        let height = |part: &str| part.parse::<Height>().map_err(|e| format!("Bad height '{}': {}", part, e));
+        let rule = TrailRule { min_climb: number(min)?, max_climb: number(max)?, start: height(start)?, end: height(end)? };
+        if rule.min_climb > rule.max_climb {
+            return Err(format!("Climb range {}..={} is empty", rule.min_climb, rule.max_climb));
+        }
+        Ok(rule)
+    }
+}
+
+fn load_grid_from_str(input: &str) -> (Heightmap, Summits, TrailHeads) {
     // parse the grid from the string
     let lines: Vec<&str> = input.lines().collect();
     let mut grid = Grid::new(lines.len(), lines[0].len());
@@ -31,13 +112,98 @@ fn load_grid_from_str(input: &str) -> (Grid<u8>, Summits, TrailHeads) {
                 }
                 _ => {}
             }
-            *grid.get_mut(i, j).unwrap() = ch.to_digit(10).unwrap() as u8;
+            // anything that is not a digit, usually '.', can't be walked on
+            *grid.get_mut(i, j).unwrap() = ch.to_digit(10);
         }
     }
     (grid, summits, trail_heads)
 }
 
-fn find_summits(grid: &Grid<u8>, summits: &Summits, trail_heads: &TrailHeads) -> usize {
+// One row per line of whitespace-separated heights, with '.' for impassable cells
+fn load_whitespace_heights(input: &str) -> Result<Heightmap, String> {
+    let rows = input.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.split_whitespace().map(parse_height).collect::<Result<Vec<_>, _>>())
+        .collect::<Result<Vec<_>, _>>()?;
+    heightmap_from_rows(rows)
+}
+
+fn parse_height(token: &str) -> Result<Option<Height>, String> {
+    match token {
+        "." => Ok(None),
+        _ => token.parse().map(Some).map_err(|e| format!("Bad height '{}': {}", token, e)),
+    }
+}
+
+fn heightmap_from_rows(rows: Vec<Vec<Option<Height>>>) -> Result<Heightmap, String> {
+    let cols = rows.first().map(Vec::len).ok_or("Heightmap is empty")?;
+    if let Some(row) = rows.iter().position(|row| row.len() != cols) {
+        return Err(format!("Row {} has {} heights, expected {}", row, rows[row].len(), cols));
+    }
+    Ok(Grid::from_vec(rows.into_iter().flatten().collect(), cols))
+}
+
+// Plain (P2) or raw (P5) greymap, raw samples are two bytes big-endian when maxval is over 255
+fn load_pgm(bytes: &[u8]) -> Result<Heightmap, String> {
+    let mut position = 0;
+    let mut header = Vec::new();
+    while header.len() < 4 {
+        header.push(pgm_token(bytes, &mut position).ok_or("PGM header is truncated")?);
+    }
+    let number = |token: &str| token.parse::<usize>().map_err(|e| format!("Bad PGM header value '{}': {}", token, e));
+    let (width, height, maxval) = (number(&header[1])?, number(&header[2])?, number(&header[3])?);
+    if width == 0 || height == 0 || maxval == 0 || maxval > 65535 {
+        return Err(format!("Unsupported PGM size {}x{} with maxval {}", width, height, maxval));
+    }
+    let samples: Vec<Option<Height>> = match header[0].as_str() {
+        "P2" => (0..width * height)
+            .map(|_| {
+                let token = pgm_token(bytes, &mut position).ok_or("PGM has too few samples")?;
+                token.parse().map(Some).map_err(|e| format!("Bad PGM sample '{}': {}", token, e))
+            })
+            .collect::<Result<_, String>>()?,
+        "P5" => {
+            // a single whitespace byte separates the header from the raster
+            let raster = bytes.get(position + 1..).ok_or("PGM has no raster")?;
+            let sample_size = if maxval > 255 { 2 } else { 1 };
+            if raster.len() < width * height * sample_size {
+                return Err("PGM has too few samples".to_string());
+            }
+            raster.chunks(sample_size)
+                .take(width * height)
+                .map(|sample| Some(sample.iter().fold(0, |value, &byte| value << 8 | byte as Height)))
+                .collect()
+        }
+        magic => return Err(format!("Not a PGM image: '{}'", magic)),
+    };
+    Ok(Grid::from_vec(samples, width))
+}
+
+// The next whitespace-separated token, skipping '#' comments to the end of the line
+fn pgm_token(bytes: &[u8], position: &mut usize) -> Option<String> {
+    loop {
+        while bytes.get(*position)?.is_ascii_whitespace() {
+            *position += 1;
+        }
+        if bytes[*position] != b'#' {
+            break;
+        }
+        while bytes.get(*position).is_some_and(|&byte| byte != b'\n') {
+            *position += 1;
+        }
+    }
+    let start = *position;
+    while bytes.get(*position).is_some_and(|byte| !byte.is_ascii_whitespace()) {
+        *position += 1;
+    }
+    Some(String::from_utf8_lossy(&bytes[start..*position]).into_owned())
+}
+
+fn cells_at_height(grid: &Heightmap, height: Height) -> HashSet<Position> {
+    grid.indexed_iter().filter(|(_, &cell)| cell == Some(height)).map(|(position, _)| position).collect()
+}
+
+fn find_summits(grid: &Heightmap, summits: &Summits, trail_heads: &TrailHeads) -> usize {
     //bfs to find the number of summits reachable from the trail heads
 
     let mut num_summits_reached = 0;
@@ -56,10 +222,10 @@ fn find_summits(grid: &Grid<u8>, summits: &Summits, trail_heads: &TrailHeads) ->
                     let new_i = (i as i32 + di) as usize;
                     let new_j = (j as i32 + dj) as usize;
 
-                    let new_height = grid.get(new_i, new_j);
+                    let new_height = grid.get(new_i, new_j).copied().flatten();
 
-                    if new_height.is_some() && *new_height.unwrap() == height + 1 {
-                        queue.push_back((new_i, new_j, *new_height.unwrap()));
+                    if new_height == Some(height + 1) {
+                        queue.push_back((new_i, new_j, height + 1));
                     }
                 }
             }
@@ -68,7 +234,7 @@ fn find_summits(grid: &Grid<u8>, summits: &Summits, trail_heads: &TrailHeads) ->
 
     num_summits_reached
 }
-fn find_trails(grid: &Grid<u8>, summits: &Summits, trail_heads: &TrailHeads) -> usize {
+fn find_trails(grid: &Heightmap, summits: &Summits, trail_heads: &TrailHeads) -> usize {
     let mut trails = 0;
 
     for &(i, j) in trail_heads.iter() {
@@ -76,7 +242,7 @@ fn find_trails(grid: &Grid<u8>, summits: &Summits, trail_heads: &TrailHeads) ->
         times_visited.insert((i, j), 1);
 
         let mut queue = VecDeque::new();
-        queue.push_back((i, j, grid.get(i, j).unwrap().clone()));
+        queue.push_back((i, j, grid.get(i, j).unwrap().unwrap()));
 
         while let Some((i, j, height)) = queue.pop_front() {
             if summits.contains(&(i, j)) {
@@ -93,7 +259,7 @@ fn find_trails(grid: &Grid<u8>, summits: &Summits, trail_heads: &TrailHeads) ->
                     let new_i = new_i as usize;
                     let new_j = new_j as usize;
 
-                    if let Some(&new_height) = grid.get(new_i, new_j) {
+                    if let Some(&Some(new_height)) = grid.get(new_i, new_j) {
                         if new_height == height + 1 {
                             if let Some(seen_count) = times_visited.get(&(new_i, new_j)) {
                                 if let Some(&current_count) = times_visited.get(&(i, j)) {
@@ -117,6 +283,107 @@ fn find_trails(grid: &Grid<u8>, summits: &Summits, trail_heads: &TrailHeads) ->
     trails
 }
 
+// Passable cells next to a position, with their heights
+fn neighbours(grid: &Heightmap, (i, j): Position) -> impl Iterator<Item = (Position, Height)> + '_ {
+    [(0, 1), (1, 0), (0, -1), (-1, 0)].into_iter().filter_map(move |(di, dj)| {
+        let new_i = i.checked_add_signed(di)?;
+        let new_j = j.checked_add_signed(dj)?;
+        grid.get(new_i, new_j).copied().flatten().map(|height| ((new_i, new_j), height))
+    })
+}
+
+// Every trail head, in reading order, with the summits that can be reached from it
+fn trail_head_summits(grid: &Heightmap, rule: &TrailRule, trail_heads: &TrailHeads) -> Vec<(Position, Summits)> {
+    let mut trail_heads: Vec<Position> = trail_heads.iter().copied().collect();
+    trail_heads.sort();
+    trail_heads.into_iter().map(|trail_head| (trail_head, reachable_summits(grid, rule, trail_head))).collect()
+}
+
+// A breadth first search, so it stays linear in the map however many trails there are.
+// Like a trail, it stops at a summit rather than walking on past it
+fn reachable_summits(grid: &Heightmap, rule: &TrailRule, trail_head: Position) -> Summits {
+    let mut summits = Summits::new();
+    if grid.get(trail_head.0, trail_head.1) != Some(&Some(rule.start)) {
+        return summits;
+    }
+    let mut visited = HashSet::from([trail_head]);
+    let mut queue = VecDeque::from([(trail_head, rule.start)]);
+    while let Some((position, height)) = queue.pop_front() {
+        if height == rule.end && position != trail_head {
+            summits.insert(position);
+            continue;
+        }
+        for (next, next_height) in neighbours(grid, position) {
+            if rule.allows(height, next_height) && visited.insert(next) {
+                queue.push_back((next, next_height));
+            }
+        }
+    }
+    summits
+}
+
+// Every distinct trail from a trail head to a summit, found depth first as the iterator is used.
+// A trail never revisits a cell, so rules allowing level steps or descents still terminate
+struct Trails<'a> {
+    grid: &'a Heightmap,
+    rule: TrailRule,
+    stack: Vec<Trail>,
+}
+
+fn trails_from<'a>(grid: &'a Heightmap, rule: &TrailRule, trail_head: Position) -> Trails<'a> {
+    let stack = if grid.get(trail_head.0, trail_head.1) == Some(&Some(rule.start)) { vec![vec![trail_head]] } else { Vec::new() };
+    Trails { grid, rule: *rule, stack }
+}
+
+impl Iterator for Trails<'_> {
+    type Item = Trail;
+
+    fn next(&mut self) -> Option<Trail> {
+        while let Some(trail) = self.stack.pop() {
+            let last = *trail.last().unwrap();
+            let height = self.grid[last].unwrap();
+            if height == self.rule.end && trail.len() > 1 {
+                return Some(trail);
+            }
+            // pushed in reverse so trails come out in neighbour order
+            let steps: Vec<Position> = neighbours(self.grid, last)
+                .filter(|&(next, next_height)| self.rule.allows(height, next_height) && !trail.contains(&next))
+                .map(|(next, _)| next)
+                .collect();
+            for &next in steps.iter().rev() {
+                let mut extended = trail.clone();
+                extended.push(next);
+                self.stack.push(extended);
+            }
+        }
+        None
+    }
+}
+
+// The map with one trail picked out: in bold green over the whole map with `ansi`,
+// otherwise just the trail's heights with every other cell as '.'. Impassable cells are always '.',
+// and maps with multi-digit heights get right-aligned, space-separated columns
+fn render_trail(grid: &Heightmap, trail: &[Position], ansi: bool) -> String {
+    let on_trail: HashSet<&Position> = trail.iter().collect();
+    let width = grid.iter().flatten().map(|height| height.to_string().len()).max().unwrap_or(1);
+    let mut result = String::new();
+    for (i, row) in grid.iter_rows().enumerate() {
+        for (j, height) in row.enumerate() {
+            if width > 1 && j > 0 {
+                result.push(' ');
+            }
+            let text = match (height, on_trail.contains(&(i, j)), ansi) {
+                (Some(height), true, true) => format!("\x1b[1;32m{:>width$}\x1b[0m", height),
+                (Some(height), true, false) | (Some(height), false, true) => format!("{:>width$}", height),
+                _ => format!("{:>width$}", '.'),
+            };
+            result.push_str(&text);
+        }
+        result.push('\n');
+    }
+    result
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -125,11 +392,11 @@ mod tests {
     fn test_can_parse_grid() {
         let grid_str = "0123\n1234\n8765\n9876";
         let (grid, summits, trail_heads) = load_grid_from_str(grid_str);
-        assert_eq!(*grid.get(0, 0).unwrap(), 0);
-        assert_eq!(*grid.get(0, 1).unwrap(), 1);
-        assert_eq!(*grid.get(0, 2).unwrap(), 2);
-        assert_eq!(*grid.get(0, 3).unwrap(), 3);
-        assert_eq!(*grid.get(1, 3).unwrap(), 4);
+        assert_eq!(*grid.get(0, 0).unwrap(), Some(0));
+        assert_eq!(*grid.get(0, 1).unwrap(), Some(1));
+        assert_eq!(*grid.get(0, 2).unwrap(), Some(2));
+        assert_eq!(*grid.get(0, 3).unwrap(), Some(3));
+        assert_eq!(*grid.get(1, 3).unwrap(), Some(4));
         assert_eq!(trail_heads.len(), 1);
         assert_eq!(summits.len(), 1);
     }
@@ -169,4 +436,108 @@ mod tests {
         let total_trails = find_trails(&grid, &summits, &trail_heads);
         assert_eq!(total_trails, 81)
     }
+
+    #[test]
+    fn test_trail_head_summits() {
+        let grid_str = std::fs::read_to_string("test_input.txt").unwrap();
+        let (grid, _, trail_heads) = load_grid_from_str(&grid_str);
+        let reachable = trail_head_summits(&grid, &TrailRule::default(), &trail_heads);
+        assert_eq!(reachable.len(), 9);
+        assert_eq!(reachable[0].0, (0, 2));
+        assert_eq!(reachable[0].1.len(), 5);
+        assert_eq!(reachable.iter().map(|(_, summits)| summits.len()).sum::<usize>(), 36);
+    }
+
+    #[test]
+    fn test_enumerate_trails() {
+        let grid_str = std::fs::read_to_string("test_input.txt").unwrap();
+        let (grid, _, trail_heads) = load_grid_from_str(&grid_str);
+        let total: usize = trail_heads.iter().map(|&trail_head| trails_from(&grid, &TrailRule::default(), trail_head).count()).sum();
+        assert_eq!(total, 81);
+        for trail in trails_from(&grid, &TrailRule::default(), (0, 2)) {
+            assert_eq!(trail.len(), 10);
+            assert!(trail.iter().enumerate().all(|(height, &position)| grid[position] == Some(height as Height)));
+        }
+        assert_eq!(trails_from(&grid, &TrailRule::default(), (0, 0)).count(), 0);
+    }
+
+    #[test]
+    fn test_render_trail() {
+        let grid_str = "0123\n1234\n8765\n9876";
+        let (grid, _, _) = load_grid_from_str(grid_str);
+        let trails: Vec<Trail> = trails_from(&grid, &TrailRule::default(), (0, 0)).collect();
+        assert_eq!(trails.len(), 16);
+        assert_eq!(render_trail(&grid, &trails[0], false), "0123\n...4\n...5\n9876\n");
+        assert!(render_trail(&grid, &trails[0], true).starts_with("\x1b[1;32m0\x1b[0m"));
+    }
+
+    #[test]
+    fn test_impassable_cells() {
+        let grid_str = "...0...\n...1...\n...2...\n6543456\n7.....7\n8.....8\n9.....9";
+        let (grid, summits, trail_heads) = load_grid_from_str(grid_str);
+        assert_eq!(grid[(0, 0)], None);
+        assert_eq!(find_summits(&grid, &summits, &trail_heads), 2);
+        assert_eq!(find_trails(&grid, &summits, &trail_heads), 2);
+        assert_eq!(render_trail(&grid, &trails_from(&grid, &TrailRule::default(), (0, 3)).next().unwrap(), false),
+            "...0...\n...1...\n...2...\n...3456\n......7\n......8\n......9\n");
+    }
+
+    #[test]
+    fn test_reachability_on_flat_ground() {
+        // level steps give far too many trails to list, but reachability only visits each cell once
+        let mut rows = vec![vec![Some(0); 40]; 40];
+        rows[39][39] = Some(9);
+        rows[0][39] = Some(9);
+        rows[1][38] = None;
+        let grid = heightmap_from_rows(rows).unwrap();
+        let level: TrailRule = "0,9,0,9".parse().unwrap();
+        let reachable = trail_head_summits(&grid, &level, &TrailHeads::from([(0, 0)]));
+        assert_eq!(reachable, vec![((0, 0), Summits::from([(39, 39), (0, 39)]))]);
+        assert!(trail_head_summits(&grid, &level, &TrailHeads::from([(39, 39)]))[0].1.is_empty());
+    }
+
+    #[test]
+    fn test_configurable_rule() {
+        assert_eq!("-1,2,10,40".parse(), Ok(TrailRule { min_climb: -1, max_climb: 2, start: 10, end: 40 }));
+        assert!("2,1,0,9".parse::<TrailRule>().is_err());
+        assert!("1,1,0".parse::<TrailRule>().is_err());
+
+        let grid = load_whitespace_heights("10 11 13\n . 12 14\n40 30 15").unwrap();
+        assert_eq!(grid[(2, 0)], Some(40));
+        let climb = "1,2,10,15".parse().unwrap();
+        let trails: Vec<Trail> = trails_from(&grid, &climb, (0, 0)).collect();
+        assert_eq!(trails, vec![vec![(0, 0), (0, 1), (0, 2), (1, 2), (2, 2)], vec![(0, 0), (0, 1), (1, 1), (1, 2), (2, 2)]]);
+        // the default rule needs a +1 step every time, so 13 -> 14 -> 15 alone is not enough
+        assert_eq!(trails_from(&grid, &TrailRule { start: 10, end: 15, ..TrailRule::default() }, (0, 0)).count(), 0);
+
+        // a descending rule walks back down without looping between two cells
+        let descend = TrailRule { min_climb: -2, max_climb: 1, start: 15, end: 12 };
+        let reachable = trail_head_summits(&grid, &descend, &cells_at_height(&grid, 15));
+        assert_eq!(reachable, vec![((2, 2), [(1, 1)].into_iter().collect())]);
+        assert_eq!(render_trail(&grid, &[(2, 2), (1, 2), (1, 1)], false), " .  .  .\n . 12 14\n .  . 15\n");
+
+        assert!(load_whitespace_heights("1 2\n3").is_err());
+        assert!(load_whitespace_heights("1 x").is_err());
+    }
+
+    #[test]
+    fn test_load_pgm() {
+        let plain = load_pgm(b"P2\n# a comment\n3 2\n300\n0 1 2\n300 299 3\n").unwrap();
+        assert_eq!((plain.rows(), plain.cols()), (2, 3));
+        assert_eq!(plain[(1, 0)], Some(300));
+
+        let mut raw = b"P5 2 2 255\n".to_vec();
+        raw.extend([0, 1, 3, 2]);
+        let raw = load_pgm(&raw).unwrap();
+        assert_eq!(raw.iter().copied().collect::<Vec<_>>(), vec![Some(0), Some(1), Some(3), Some(2)]);
+        assert_eq!(trails_from(&raw, &TrailRule { end: 3, ..TrailRule::default() }, (0, 0)).count(), 1);
+
+        let mut wide = b"P5 1 1 1000\n".to_vec();
+        wide.extend([3, 232]);
+        assert_eq!(load_pgm(&wide).unwrap()[(0, 0)], Some(1000));
+
+        assert!(load_pgm(b"P5 2 2 255\n\x00").is_err());
+        assert!(load_pgm(b"P6 1 1 255\n\x00\x00\x00").is_err());
+        assert!(load_pgm(b"P2 2").is_err());
+    }
 }
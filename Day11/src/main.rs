@@ -1,43 +1,300 @@
+use num::{BigUint, Zero};
 use std::collections::HashMap;
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::str::FromStr;
 
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    // --rules=file swaps the puzzle's rules for a rule set in the config format below
+    let rules = match args.iter().find_map(|arg| arg.strip_prefix("--rules=")) {
+        Some(path) => std::fs::read_to_string(path).expect("Failed to read rules").parse().expect("Bad rules"),
+        None => RuleSet::standard(),
+    };
     let input_file = "input.txt";
-    let input = load_input(input_file);
-    let input2 = input.clone();
-    let result = blink_count(input, 25);
-    println!("The number of stones after 25 blinks is: {}", result);
-    let result = blink_count(input2, 75);
-    println!("The number of stones after 75 blinks is: {}", result);
+    // --big counts with arbitrary-precision stones, for rules whose values outgrow usize
+    let big = args.iter().any(|arg| arg == "--big");
+    if big {
+        let input: Vec<BigUint> = load_input(input_file);
+        println!("The number of stones after 25 blinks is: {}", blink_count_big(&rules, &input, 25));
+        println!("The number of stones after 75 blinks is: {}", blink_count_big(&rules, &input, 75));
+    } else {
+        let input: Vec<usize> = load_input(input_file);
+        let input2 = input.clone();
+        let result = blink_count(&rules, input, 25);
+        println!("The number of stones after 25 blinks is: {}", result);
+        let result = blink_count(&rules, input2, 75);
+        println!("The number of stones after 75 blinks is: {}", result);
+    }
+    // --stats=n prints the number of distinct values and stones after each of n blinks
+    if let Some(blinks) = args.iter().find_map(|arg| arg.strip_prefix("--stats=")) {
+        let blinks = blinks.parse().expect("Bad blink count");
+        if big {
+            print_stats::<BigUint>(&rules, &load_input(input_file), blinks);
+        } else {
+            print_stats::<usize>(&rules, &load_input(input_file), blinks);
+        }
+    }
+    // --show=n prints the stones themselves after n blinks
+    if let Some(blinks) = args.iter().find_map(|arg| arg.strip_prefix("--show=")) {
+        println!("{:?}", blink(&rules, load_input(input_file), blinks.parse().expect("Bad blink count")));
+    }
+}
+
+// The puzzle's rules in the config format: one rule per line as `predicate -> outputs`,
+// tried in order with the first match winning
+const STANDARD_RULES: &str = "\
+# engraved with 0: becomes 1
+0 -> 1
+# even number of digits: split into the left and right halves
+even-digits -> left right
+# anything else is multiplied by 2024
+* -> *2024
+";
+
+// When a rule applies to a stone
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Predicate {
+    // `*`
+    Always,
+    // a number, e.g. `0`
+    Equals(usize),
+    // `even-digits` / `odd-digits`
+    EvenDigits,
+    OddDigits,
+    // `divisible:N`
+    DivisibleBy(usize),
+}
+
+// One stone the rule leaves in place of the old one
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Output {
+    // a number, e.g. `1`
+    Constant(usize),
+    // `*N`
+    Multiply(usize),
+    // `+N`
+    Add(usize),
+    // `left` / `right`: the first or second half of the digits, the middle digit going left when odd
+    LeftHalf,
+    RightHalf,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Rule {
+    predicate: Predicate,
+    outputs: Vec<Output>,
+}
+
+// Rules tried in order; a stone no rule matches stays as it is
+#[derive(Debug, Clone, PartialEq)]
+struct RuleSet {
+    rules: Vec<Rule>,
+}
+
+// The number engraved on a stone: usize for speed, or BigUint when values grow without bound
+trait Stone: Clone + Eq + Hash + Debug + FromStr {
+    fn from_usize(value: usize) -> Self;
+    fn digit_count(&self) -> u32;
+    fn is_multiple_of(&self, divisor: usize) -> bool;
+    fn multiply(&self, factor: usize) -> Self;
+    fn add(&self, amount: usize) -> Self;
+    // (left, right) halves of the digits, the middle digit going left when odd
+    fn split(&self) -> (Self, Self);
 }
 
-fn load_input(p0: &str) -> Vec<usize> {
+impl Stone for usize {
+    fn from_usize(value: usize) -> Self {
+        value
+    }
+
+    fn digit_count(&self) -> u32 {
+        self.checked_ilog10().unwrap_or(0) + 1
+    }
+
+    fn is_multiple_of(&self, divisor: usize) -> bool {
+        usize::is_multiple_of(*self, divisor)
+    }
+
+    fn multiply(&self, factor: usize) -> Self {
+        self.checked_mul(factor).expect("Stone overflowed usize, use the big-integer mode")
+    }
+
+    fn add(&self, amount: usize) -> Self {
+        self.checked_add(amount).expect("Stone overflowed usize, use the big-integer mode")
+    }
+
+    fn split(&self) -> (Self, Self) {
+        let half = 10usize.pow(self.digit_count() / 2);
+        (self / half, self % half)
+    }
+}
+
+impl Stone for BigUint {
+    fn from_usize(value: usize) -> Self {
+        BigUint::from(value)
+    }
+
+    fn digit_count(&self) -> u32 {
+        self.to_string().len() as u32
+    }
+
+    fn is_multiple_of(&self, divisor: usize) -> bool {
+        (self % divisor).is_zero()
+    }
+
+    fn multiply(&self, factor: usize) -> Self {
+        self * factor
+    }
+
+    fn add(&self, amount: usize) -> Self {
+        self + amount
+    }
+
+    fn split(&self) -> (Self, Self) {
+        let half = BigUint::from(10u32).pow(self.digit_count() / 2);
+        (self / &half, self % &half)
+    }
+}
+
+impl Predicate {
+    fn matches<S: Stone>(&self, stone: &S) -> bool {
+        match *self {
+            Predicate::Always => true,
+            Predicate::Equals(value) => *stone == S::from_usize(value),
+            Predicate::EvenDigits => stone.digit_count().is_multiple_of(2),
+            Predicate::OddDigits => !stone.digit_count().is_multiple_of(2),
+            Predicate::DivisibleBy(divisor) => stone.is_multiple_of(divisor),
+        }
+    }
+}
+
+impl Output {
+    fn apply<S: Stone>(&self, stone: &S) -> S {
+        match *self {
+            Output::Constant(value) => S::from_usize(value),
+            Output::Multiply(factor) => stone.multiply(factor),
+            Output::Add(amount) => stone.add(amount),
+            Output::LeftHalf => stone.split().0,
+            Output::RightHalf => stone.split().1,
+        }
+    }
+}
+
+impl RuleSet {
+    fn standard() -> Self {
+        STANDARD_RULES.parse().unwrap()
+    }
+
+    fn apply<S: Stone>(&self, stone: &S) -> Vec<S> {
+        match self.rules.iter().find(|rule| rule.predicate.matches(stone)) {
+            Some(rule) => rule.outputs.iter().map(|output| output.apply(stone)).collect(),
+            None => vec![stone.clone()],
+        }
+    }
+}
+
+impl FromStr for Predicate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "*" => Ok(Predicate::Always),
+            "even-digits" => Ok(Predicate::EvenDigits),
+            "odd-digits" => Ok(Predicate::OddDigits),
+            _ => match s.strip_prefix("divisible:") {
+                Some(divisor) => match parse_number(divisor)? {
+                    0 => Err("Can't test divisibility by 0".to_string()),
+                    divisor => Ok(Predicate::DivisibleBy(divisor)),
+                },
+                None => parse_number(s).map(Predicate::Equals),
+            },
+        }
+    }
+}
+
+impl FromStr for Output {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "left" => Ok(Output::LeftHalf),
+            "right" => Ok(Output::RightHalf),
+            _ => {
+                if let Some(factor) = s.strip_prefix('*') {
+                    parse_number(factor).map(Output::Multiply)
+                } else if let Some(amount) = s.strip_prefix('+') {
+                    parse_number(amount).map(Output::Add)
+                } else {
+                    parse_number(s).map(Output::Constant)
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for RuleSet {
+    type Err = String;
+
+    // blank lines and `#` comments are ignored
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rules = Vec::new();
+        for (number, line) in s.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (predicate, outputs) = line
+                .split_once("->")
+                .ok_or_else(|| format!("Line {}: expected `predicate -> outputs`", number + 1))?;
+            let rule = Rule {
+                predicate: predicate.trim().parse().map_err(|e| format!("Line {}: {}", number + 1, e))?,
+                outputs: outputs
+                    .split_whitespace()
+                    .map(str::parse)
+                    .collect::<Result<_, _>>()
+                    .map_err(|e| format!("Line {}: {}", number + 1, e))?,
+            };
+            rules.push(rule);
+        }
+        Ok(RuleSet { rules })
+    }
+}
+
+fn parse_number(s: &str) -> Result<usize, String> {
+    s.parse().map_err(|e| format!("Bad number '{}': {}", s, e))
+}
+
+fn load_input<S: Stone>(p0: &str) -> Vec<S> {
     std::fs::read_to_string(p0)
         .expect("Failed to read file")
-        .split(" ")
-        .map(|x| x.parse::<usize>().unwrap())
+        .split_whitespace()
+        .map(|x| x.parse::<S>().ok().expect("Bad stone"))
         .collect()
 }
 
-fn blink_count(stones: Vec<usize>, blinks: usize) -> usize {
+fn blink_count(rules: &RuleSet, stones: Vec<usize>, blinks: usize) -> usize {
     let mut memo = HashMap::new();
-    stones.iter().map(|&stone| transform_count(stone, blinks, &mut memo)).sum()
+    stones.iter().map(|&stone| transform_count(rules, stone, blinks, &mut memo)).sum()
 }
 
-fn blink(stones: Vec<usize>, blinks: usize) -> Vec<usize> {
+fn blink(rules: &RuleSet, stones: Vec<usize>, blinks: usize) -> Vec<usize> {
     let mut memo = HashMap::new();
     let mut current_stones = stones;
 
     for _ in 0..blinks {
         current_stones = current_stones
             .into_iter()
-            .flat_map(|stone| transform(stone, &mut memo))
+            .flat_map(|stone| transform(rules, stone, &mut memo))
             .collect();
     }
 
     current_stones
 }
 
+// The memo is only valid for the one rule set it was filled with
 fn transform_count(
+    rules: &RuleSet,
     stone: usize,
     remaining_blinks: usize,
     memory: &mut HashMap<(usize, usize), usize>,
@@ -50,9 +307,10 @@ fn transform_count(
         return cached;
     }
 
-    let result = process_transform(stone)
+    let result = rules
+        .apply(&stone)
         .into_iter()
-        .map(|next_stone| transform_count(next_stone, remaining_blinks - 1, memory))
+        .map(|next_stone| transform_count(rules, next_stone, remaining_blinks - 1, memory))
         .sum();
 
     memory.insert((stone, remaining_blinks), result);
@@ -60,6 +318,7 @@ fn transform_count(
 }
 
 fn transform(
+    rules: &RuleSet,
     stone: usize,
     memory: &mut HashMap<(usize, usize), Vec<usize>>,
 ) -> Vec<usize> {
@@ -67,22 +326,109 @@ fn transform(
         return cached.clone();
     }
 
-    let result = process_transform(stone);
+    let result = rules.apply(&stone);
     memory.insert((stone, 1), result.clone());
     result
 }
 
-fn process_transform(stone: usize) -> Vec<usize> {
-    if stone == 0 {
-        vec![1]
-    } else if stone.to_string().len() % 2 == 0 {
-        let digits = stone.to_string();
-        let mid = digits.len() / 2;
-        let left: usize = digits[..mid].parse().unwrap();
-        let right: usize = digits[mid..].parse().unwrap();
-        vec![left, right]
-    } else {
-        vec![stone * 2024]
+// How many stones carry each distinct value
+type Histogram<S> = HashMap<S, BigUint>;
+
+fn histogram<S: Stone>(stones: &[S]) -> Histogram<S> {
+    let mut histogram = Histogram::new();
+    for stone in stones {
+        *histogram.entry(stone.clone()).or_default() += 1u32;
+    }
+    histogram
+}
+
+fn blink_histogram<S: Stone>(rules: &RuleSet, histogram: &Histogram<S>) -> Histogram<S> {
+    let mut next = Histogram::new();
+    for (stone, count) in histogram {
+        for next_stone in rules.apply(stone) {
+            *next.entry(next_stone).or_default() += count;
+        }
+    }
+    next
+}
+
+// The histogram after each blink in turn, never ending
+struct Histograms<'a, S> {
+    rules: &'a RuleSet,
+    current: Histogram<S>,
+}
+
+fn histograms<'a, S: Stone>(rules: &'a RuleSet, stones: &[S]) -> Histograms<'a, S> {
+    Histograms { rules, current: histogram(stones) }
+}
+
+impl<S: Stone> Iterator for Histograms<'_, S> {
+    type Item = Histogram<S>;
+
+    fn next(&mut self) -> Option<Histogram<S>> {
+        self.current = blink_histogram(self.rules, &self.current);
+        Some(self.current.clone())
+    }
+}
+
+fn blink_count_big<S: Stone>(rules: &RuleSet, stones: &[S], blinks: usize) -> BigUint {
+    let last = match blinks {
+        0 => histogram(stones),
+        _ => histograms(rules, stones).nth(blinks - 1).unwrap(),
+    };
+    last.values().sum()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct BlinkStats {
+    blink: usize,
+    distinct: usize,
+    stones: BigUint,
+}
+
+impl BlinkStats {
+    fn of<S>(blink: usize, histogram: &Histogram<S>) -> Self {
+        BlinkStats { blink, distinct: histogram.len(), stones: histogram.values().sum() }
+    }
+
+    // stones after this blink per stone before it
+    fn growth_rate(&self, previous: &BlinkStats) -> f64 {
+        ratio(&self.stones, &previous.stones)
+    }
+}
+
+// Big integer ratio kept accurate by dropping the same number of low bits from both sides
+fn ratio(numerator: &BigUint, denominator: &BigUint) -> f64 {
+    let shift = numerator.bits().max(denominator.bits()).saturating_sub(64);
+    let to_f64 = |value: &BigUint| (value >> shift).to_u64_digits().first().copied().unwrap_or(0) as f64;
+    to_f64(numerator) / to_f64(denominator)
+}
+
+// Stats for blinks 1..=blinks, and the first blink from which the number of distinct
+// values stays the same for `window` blinks, if that happens within them
+fn find_stabilisation<S: Stone>(rules: &RuleSet, stones: &[S], blinks: usize, window: usize) -> (Vec<BlinkStats>, Option<usize>) {
+    let stats: Vec<BlinkStats> = histograms(rules, stones)
+        .take(blinks)
+        .enumerate()
+        .map(|(blink, histogram)| BlinkStats::of(blink + 1, &histogram))
+        .collect();
+    let stable = stats
+        .windows(window.max(1))
+        .find(|run| run.iter().all(|stats| stats.distinct == run[0].distinct))
+        .map(|run| run[0].blink);
+    (stats, stable)
+}
+
+fn print_stats<S: Stone>(rules: &RuleSet, stones: &[S], blinks: usize) {
+    let (stats, stable) = find_stabilisation(rules, stones, blinks, 10);
+    let mut previous = BlinkStats::of(0, &histogram(stones));
+    for current in stats {
+        println!("{:>6} {:>8} distinct {:>12.6}x  {} stones", current.blink, current.distinct, current.growth_rate(&previous), current.stones);
+        previous = current;
+    }
+    match stable {
+        Some(blink) => println!("Distinct values stable from blink {}", blink),
+        None => println!("Distinct values still changing"),
     }
 }
 
@@ -92,14 +438,14 @@ mod tests {
 
     #[test]
     fn test_load_input() {
-        let input = load_input("test_input.txt");
+        let input: Vec<usize> = load_input("test_input.txt");
         assert_eq!(input.len(), 2);
     }
 
     #[test]
     fn test_transform() {
         let input = 253000;
-        let output = transform(input, &mut HashMap::new());
+        let output = transform(&RuleSet::standard(), input, &mut HashMap::new());
         let expected = vec![253, 0];
         assert_eq!(output, expected);
     }
@@ -107,7 +453,7 @@ mod tests {
     #[test]
     fn test_1_to_3_blinks() {
         let input = vec![125, 17];
-        let output = blink(input, 3);
+        let output = blink(&RuleSet::standard(), input, 3);
         let expected = vec![512072, 1, 20, 24, 28676032];
         assert_eq!(output, expected);
     }
@@ -115,7 +461,89 @@ mod tests {
     #[test]
     fn test_test_input_to_25_blinks_with_count() {
         let input = load_input("test_input.txt");
-        let output = blink_count(input, 25);
+        let output = blink_count(&RuleSet::standard(), input, 25);
         assert_eq!(output, 55312);
     }
+
+    #[test]
+    fn test_parse_rules() {
+        let rules = RuleSet::standard();
+        assert_eq!(rules.rules.len(), 3);
+        assert_eq!(rules.rules[1], Rule { predicate: Predicate::EvenDigits, outputs: vec![Output::LeftHalf, Output::RightHalf] });
+        assert_eq!(rules.apply(&0), vec![1]);
+        assert_eq!(rules.apply(&1000), vec![10, 0]);
+        assert_eq!(rules.apply(&9), vec![18216]);
+
+        assert!("0 1".parse::<RuleSet>().is_err());
+        assert!("x -> 1".parse::<RuleSet>().is_err());
+        assert!("divisible:0 -> 1".parse::<RuleSet>().is_err());
+        assert_eq!("# nothing\n\n".parse(), Ok(RuleSet { rules: Vec::new() }));
+    }
+
+    #[test]
+    fn test_custom_rules() {
+        // split odd-digit numbers with the middle digit going left, triple multiples of 3, otherwise add 1
+        let rules: RuleSet = "odd-digits -> left right\ndivisible:3 -> *3 *3 # two copies\n* -> +1".parse().unwrap();
+        assert_eq!(rules.apply(&123), vec![12, 3]);
+        assert_eq!(rules.apply(&12), vec![36, 36]);
+        assert_eq!(rules.apply(&10), vec![11]);
+        assert_eq!(blink(&rules, vec![123], 2), vec![36, 36, 3, 0]);
+        for blinks in 0..12 {
+            assert_eq!(blink_count(&rules, vec![123, 48], blinks), blink(&rules, vec![123, 48], blinks).len());
+        }
+
+        // with no rule matching, stones are left alone
+        let rules: RuleSet = "0 -> 1 1".parse().unwrap();
+        assert_eq!(blink(&rules, vec![0, 5], 2), vec![1, 1, 5]);
+    }
+
+    #[test]
+    fn test_big_stones() {
+        // doubling outgrows usize long before 100 blinks
+        let rules: RuleSet = "* -> *2".parse().unwrap();
+        let stones = vec![BigUint::from(3u32)];
+        let last = histograms(&rules, &stones).nth(99).unwrap();
+        assert_eq!(last.keys().collect::<Vec<_>>(), vec![&(BigUint::from(3u32) << 100)]);
+        assert!(std::panic::catch_unwind(|| blink_count(&rules, vec![3], 100)).is_err());
+
+        let rules = RuleSet::standard();
+        let big: Vec<BigUint> = load_input("test_input.txt");
+        assert_eq!(blink_count_big(&rules, &big, 25), BigUint::from(55312u32));
+        assert_eq!(blink_count_big(&rules, &big, 0), BigUint::from(2u32));
+        let input: Vec<usize> = load_input("test_input.txt");
+        assert_eq!(blink_count_big(&rules, &input, 40), BigUint::from(blink_count(&rules, input, 40)));
+    }
+
+    #[test]
+    fn test_histograms() {
+        let rules = RuleSet::standard();
+        let after_six = histograms(&rules, &[125usize, 17]).nth(5).unwrap();
+        let stones = blink(&rules, vec![125, 17], 6);
+        assert_eq!(after_six, histogram(&stones));
+        assert_eq!(after_six[&2], BigUint::from(4u32));
+        assert_eq!(after_six.values().sum::<BigUint>(), BigUint::from(22u32));
+    }
+
+    #[test]
+    fn test_stabilisation() {
+        let rules = RuleSet::standard();
+        let (stats, stable) = find_stabilisation(&rules, &[125usize, 17], 150, 20);
+        assert_eq!(stats.len(), 150);
+        assert_eq!(stats[24].stones, BigUint::from(55312u32));
+        let stable = stable.unwrap();
+        assert_eq!(stats[stable - 1].distinct, stats[149].distinct);
+        assert_ne!(stats[stable - 2].distinct, stats[stable - 1].distinct);
+        let growth = stats[149].growth_rate(&stats[148]);
+        assert!(growth > 1.4 && growth < 1.6, "{}", growth);
+
+        let (big_stats, big_stable) = find_stabilisation(&rules, &[BigUint::from(125u32), BigUint::from(17u32)], 150, 20);
+        assert_eq!(big_stats, stats);
+        assert_eq!(big_stable, Some(stable));
+
+        // leaving 0 alone is stable at once, while always making new values never settles
+        let (_, stable) = find_stabilisation(&"0 -> 0".parse().unwrap(), &[0usize], 5, 3);
+        assert_eq!(stable, Some(1));
+        let (_, stable) = find_stabilisation(&"* -> +1 +2".parse().unwrap(), &[0usize], 8, 3);
+        assert_eq!(stable, None);
+    }
 }
@@ -1,15 +1,150 @@
-use std::collections::{HashSet};
+use std::collections::{HashMap, HashSet};
 use pathfinding::grid::Grid;
 
 fn main() {
-
+    let farm = Farm::load_input("input.txt");
+    println!("Part1: {}", farm.total_price());
+    println!("Part2: {}", farm.total_bulk_price());
+    let args: Vec<String> = std::env::args().collect();
+    let regions = farm.polygons();
+    let tree = ContainmentTree::of(&regions, &farm.regions());
+    if args.iter().any(|arg| arg == "--tree") {
+        print!("{}", tree.render(&regions));
+    }
+    if let Some(path) = args.iter().find_map(|arg| arg.strip_prefix("--svg=")) {
+        std::fs::write(path, to_svg(&regions, farm.grid.width, farm.grid.height, 10)).unwrap();
+    }
+    if let Some(path) = args.iter().find_map(|arg| arg.strip_prefix("--geojson=")) {
+        std::fs::write(path, to_geojson(&regions, &tree)).unwrap();
+    }
+    // --edit=x,y,plant, repeatable, replants one cell and prints the prices after it
+    let mut farm = farm;
+    for edit in args.iter().filter_map(|arg| arg.strip_prefix("--edit=")) {
+        let parts: Vec<&str> = edit.split(',').collect();
+        let cell = (parts[0].parse().expect("Bad x"), parts[1].parse().expect("Bad y"));
+        let prices = farm.set_plant(cell, parts[2].chars().next().expect("No plant"));
+        println!("{}: {} / {}, region now {:?}", edit, prices.price, prices.bulk_price, farm.region_stats(cell));
+    }
 }
 
+// (x, y) as in pathfinding's Grid: x is the column, y the row
 type Cell = (usize, usize);
 type Plot = HashSet<Cell>;
 
 struct Farm {
+    // every cell is a vertex, so neighbours and flood fills come straight from the grid
+    grid: Grid,
+    plants: Vec<Vec<char>>,
+    index: RegionIndex,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct RegionStats {
+    area: usize,
+    perimeter: usize,
+    sides: usize,
+}
+
+impl RegionStats {
+    fn price(&self) -> usize {
+        self.area * self.perimeter
+    }
+
+    fn bulk_price(&self) -> usize {
+        self.area * self.sides
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Prices {
+    price: usize,
+    bulk_price: usize,
+}
+
+// Regions kept up to date under edits: a union-find over region labels, with each cell's
+// share of its region's perimeter and sides so an edit only recounts the cells around it
+#[derive(Debug, Default)]
+struct RegionIndex {
+    // label per cell, row-major over the grid's width; None where a short row has no cell
+    labels: Vec<Option<usize>>,
+    parent: Vec<usize>,
+    size: Vec<usize>,
+    // keyed by root label
+    stats: HashMap<usize, RegionStats>,
+    // (fence segments, corners) each cell adds to its region
+    shares: Vec<(usize, usize)>,
+    prices: Prices,
+}
+
+impl RegionIndex {
+    fn new_label(&mut self) -> usize {
+        self.parent.push(self.parent.len());
+        self.size.push(1);
+        self.parent.len() - 1
+    }
 
+    // Relabels every region with just its root, dropping labels that no cell reaches any more
+    fn compact(&mut self) {
+        let mut relabel: HashMap<usize, usize> = HashMap::new();
+        let (mut parent, mut size) = (Vec::new(), Vec::new());
+        for index in 0..self.labels.len() {
+            let Some(label) = self.labels[index] else { continue };
+            let root = self.find(label);
+            let label = *relabel.entry(root).or_insert_with(|| {
+                parent.push(parent.len());
+                size.push(0);
+                parent.len() - 1
+            });
+            size[label] += 1;
+            self.labels[index] = Some(label);
+        }
+        self.stats = self.stats.drain().filter_map(|(root, stats)| Some((*relabel.get(&root)?, stats))).collect();
+        (self.parent, self.size) = (parent, size);
+    }
+
+    fn find(&mut self, label: usize) -> usize {
+        let mut root = label;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        let mut label = label;
+        while self.parent[label] != root {
+            (label, self.parent[label]) = (self.parent[label], root);
+        }
+        root
+    }
+
+    // changes a region's stats, keeping the running prices in step
+    fn adjust(&mut self, root: usize, change: impl FnOnce(&mut RegionStats)) {
+        let stats = self.stats.entry(root).or_default();
+        self.prices.price -= stats.price();
+        self.prices.bulk_price -= stats.bulk_price();
+        change(stats);
+        self.prices.price += stats.price();
+        self.prices.bulk_price += stats.bulk_price();
+        if *stats == RegionStats::default() {
+            self.stats.remove(&root);
+        }
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (mut a, mut b) = (self.find(a), self.find(b));
+        if a == b {
+            return;
+        }
+        if self.size[a] < self.size[b] {
+            (a, b) = (b, a);
+        }
+        self.parent[b] = a;
+        self.size[a] += self.size[b];
+        let mut merged = RegionStats::default();
+        self.adjust(b, |stats| merged = std::mem::take(stats));
+        self.adjust(a, |stats| {
+            stats.area += merged.area;
+            stats.perimeter += merged.perimeter;
+            stats.sides += merged.sides;
+        });
+    }
 }
 
 impl Farm {
@@ -21,11 +156,503 @@ impl Farm {
     }
 
     fn load_pathfinding_grid(input: &str) -> Self {
+        let plants: Vec<Vec<char>> = input
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.trim().chars().collect())
+            .collect();
+        let width = plants.iter().map(Vec::len).max().unwrap_or(0);
+        let mut grid = Grid::new(width, plants.len());
+        for (y, row) in plants.iter().enumerate() {
+            for x in 0..row.len() {
+                grid.add_vertex((x, y));
+            }
+        }
+        let mut farm = Farm { grid, plants, index: RegionIndex::default() };
+        farm.build_index();
+        farm
+    }
+
+    // One full flood fill to label the regions, after which edits keep the index current
+    fn build_index(&mut self) {
+        let mut index = RegionIndex { labels: vec![None; self.grid.width * self.grid.height], ..RegionIndex::default() };
+        index.shares = vec![(0, 0); index.labels.len()];
+        for plot in self.regions() {
+            let label = index.new_label();
+            index.size[label] = plot.len();
+            let mut stats = RegionStats { area: plot.len(), ..RegionStats::default() };
+            for &cell in &plot {
+                let share = (self.cell_perimeter(cell), self.cell_corners(cell));
+                index.labels[self.cell_index(cell)] = Some(label);
+                index.shares[self.cell_index(cell)] = share;
+                stats.perimeter += share.0;
+                stats.sides += share.1;
+            }
+            index.adjust(label, |current| *current = stats);
+        }
+        self.index = index;
+    }
+
+    fn cell_index(&self, (x, y): Cell) -> usize {
+        y * self.grid.width + x
+    }
+
+    fn same_plant(&self, cell: Cell, dx: isize, dy: isize) -> bool {
+        offset(cell, dx, dy).is_some_and(|next| self.plant(next) == self.plant(cell))
+    }
+
+    // The cell's fence segments
+    fn cell_perimeter(&self, cell: Cell) -> usize {
+        [(1, 0), (-1, 0), (0, 1), (0, -1)].into_iter().filter(|&(dx, dy)| !self.same_plant(cell, dx, dy)).count()
+    }
+
+    // The region boundary's corners at this cell, outside corners and inside ones
+    fn cell_corners(&self, cell: Cell) -> usize {
+        [(1, 1), (1, -1), (-1, 1), (-1, -1)]
+            .into_iter()
+            .filter(|&(dx, dy)| {
+                let (across, down) = (self.same_plant(cell, dx, 0), self.same_plant(cell, 0, dy));
+                (!across && !down) || (across && down && !self.same_plant(cell, dx, dy))
+            })
+            .count()
+    }
+
+    fn root(&mut self, cell: Cell) -> usize {
+        let label = self.index.labels[self.cell_index(cell)].expect("Cell is on the farm");
+        self.index.find(label)
+    }
+
+    // Area, perimeter and sides of the region containing `cell`, from the index
+    fn region_stats(&mut self, cell: Cell) -> RegionStats {
+        let root = self.root(cell);
+        self.index.stats[&root]
+    }
+
+    fn prices(&self) -> Prices {
+        self.index.prices
+    }
+
+    // Changes one plot's plant and returns the new prices. Only the cell's 3x3 neighbourhood
+    // is recounted; the old region is flood filled again only when it may have split
+    fn set_plant(&mut self, cell: Cell, kind: char) -> Prices {
+        let old = self.plant(cell).expect("Cell is outside the farm");
+        if old == kind {
+            return self.prices();
+        }
+        // every edit adds a label, so tidy up once they outnumber the cells
+        if self.index.parent.len() > 2 * self.index.labels.len() {
+            self.index.compact();
+        }
+        let around: Vec<Cell> = (-1..=1)
+            .flat_map(|dy| (-1..=1).filter_map(move |dx| offset(cell, dx, dy)))
+            .filter(|&next| self.plant(next).is_some())
+            .collect();
+        for &next in &around {
+            let root = self.root(next);
+            let next_index = self.cell_index(next);
+            let share = std::mem::take(&mut self.index.shares[next_index]);
+            self.index.adjust(root, |stats| {
+                stats.perimeter -= share.0;
+                stats.sides -= share.1;
+            });
+        }
+        let old_root = self.root(cell);
+        self.index.adjust(old_root, |stats| stats.area -= 1);
+        let old_neighbours: Vec<Cell> = self.grid.neighbours(cell).into_iter().filter(|&next| self.plant(next) == Some(old)).collect();
+        self.plants[cell.1][cell.0] = kind;
+        let separated = self.ring_groups(cell, old, &old_neighbours);
+        if separated.len() > 1 {
+            self.split(old_root, old, &separated);
+        }
+
+        let label = self.index.new_label();
+        let cell_index = self.cell_index(cell);
+        self.index.labels[cell_index] = Some(label);
+        self.index.adjust(label, |stats| stats.area = 1);
+        for next in self.grid.neighbours(cell) {
+            if self.plant(next) == Some(kind) {
+                let other = self.root(next);
+                self.index.union(label, other);
+            }
+        }
+
+        for &next in &around {
+            let share = (self.cell_perimeter(next), self.cell_corners(next));
+            let next_index = self.cell_index(next);
+            self.index.shares[next_index] = share;
+            let root = self.root(next);
+            self.index.adjust(root, |stats| {
+                stats.perimeter += share.0;
+                stats.sides += share.1;
+            });
+        }
+        self.prices()
+    }
+
+    // One of `neighbours` from each run of `old` plants in the eight cells around `cell`.
+    // Neighbours in the same run are still joined, so the region can only have split
+    // when there is more than one
+    fn ring_groups(&self, cell: Cell, old: char, neighbours: &[Cell]) -> Vec<Cell> {
+        const RING: [(isize, isize); 8] = [(-1, -1), (0, -1), (1, -1), (1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0)];
+        let is_old = |(dx, dy): (isize, isize)| offset(cell, dx, dy).is_some_and(|next| self.plant(next) == Some(old));
+        // start the walk just after a gap, so no run wraps around the end
+        let Some(gap) = RING.iter().position(|&step| !is_old(step)) else {
+            return neighbours.iter().take(1).copied().collect();
+        };
+        let mut groups = Vec::new();
+        let mut in_group = false;
+        for &(dx, dy) in RING.iter().cycle().skip(gap + 1).take(RING.len()) {
+            if !is_old((dx, dy)) {
+                in_group = false;
+            } else if let Some(next) = offset(cell, dx, dy).filter(|next| neighbours.contains(next)) {
+                if !in_group {
+                    groups.push(next);
+                    in_group = true;
+                }
+            }
+        }
+        groups
+    }
+
+    // Relabels what is left of a region if removing a cell cut it into pieces
+    fn split(&mut self, old_root: usize, old: char, neighbours: &[Cell]) {
+        let mut pieces: Vec<Plot> = Vec::new();
+        for &start in neighbours {
+            if !pieces.iter().any(|piece| piece.contains(&start)) {
+                pieces.push(self.grid.bfs_reachable(start, |next| self.plant(next) == Some(old)).into_iter().collect());
+            }
+        }
+        if pieces.len() == 1 {
+            return;
+        }
+        self.index.adjust(old_root, |stats| *stats = RegionStats::default());
+        for piece in pieces {
+            let label = self.index.new_label();
+            self.index.size[label] = piece.len();
+            let mut stats = RegionStats { area: piece.len(), ..RegionStats::default() };
+            for &cell in &piece {
+                let cell_index = self.cell_index(cell);
+                self.index.labels[cell_index] = Some(label);
+                stats.perimeter += self.index.shares[cell_index].0;
+                stats.sides += self.index.shares[cell_index].1;
+            }
+            self.index.adjust(label, |current| *current = stats);
+        }
+    }
+
+    fn plant(&self, (x, y): Cell) -> Option<char> {
+        self.plants.get(y)?.get(x).copied()
+    }
+
+    // All the cells of the same plant connected to `cell`
+    fn region(&self, cell: Cell) -> Plot {
+        let Some(kind) = self.plant(cell) else {
+            return Plot::new();
+        };
+        self.grid.bfs_reachable(cell, |next| self.plant(next) == Some(kind)).into_iter().collect()
+    }
+
+    // Every region, ordered by its first cell in reading order
+    fn regions(&self) -> Vec<Plot> {
+        let mut seen = HashSet::new();
+        let mut regions = Vec::new();
+        for (y, row) in self.plants.iter().enumerate() {
+            for x in 0..row.len() {
+                if !seen.contains(&(x, y)) {
+                    let region = self.region((x, y));
+                    seen.extend(region.iter().copied());
+                    regions.push(region);
+                }
+            }
+        }
+        regions
+    }
+
+    // Each region's outline, in the same order as `regions`
+    fn polygons(&self) -> Vec<RegionPolygon> {
+        self.regions()
+            .iter()
+            .map(|plot| RegionPolygon { plant: self.plant(*plot.iter().next().unwrap()).unwrap(), polygon: Polygon::of(plot) })
+            .collect()
+    }
+
+    fn total_price(&self) -> usize {
+        self.regions().iter().map(price).sum()
+    }
+
+    fn total_bulk_price(&self) -> usize {
+        self.regions().iter().map(bulk_price).sum()
+    }
+}
+
+fn offset((x, y): Cell, dx: isize, dy: isize) -> Option<Cell> {
+    Some((x.checked_add_signed(dx)?, y.checked_add_signed(dy)?))
+}
+
+fn contains(plot: &Plot, cell: Option<Cell>) -> bool {
+    cell.is_some_and(|cell| plot.contains(&cell))
+}
+
+// Fence segments: every cell edge not shared with another cell of the plot
+fn perimeter(plot: &Plot) -> usize {
+    Polygon::of(plot).perimeter()
+}
+
+// Straight fence sides: each turn of a boundary starts a new one
+fn sides(plot: &Plot) -> usize {
+    Polygon::of(plot).sides()
+}
+
+fn price(plot: &Plot) -> usize {
+    plot.len() * perimeter(plot)
+}
+
+// The price with the bulk discount, paying per side rather than per fence segment
+fn bulk_price(plot: &Plot) -> usize {
+    plot.len() * sides(plot)
+}
 
-        Farm {  }
+// A point where cell edges meet, (x, y) from the top left of the farm
+type Corner = (usize, usize);
+// A closed boundary as the corners where it turns. Outer boundaries run clockwise
+// on screen (y down) and holes anticlockwise, so the region is always on the right
+type Ring = Vec<Corner>;
+
+#[derive(Debug, Clone, PartialEq)]
+struct Polygon {
+    outer: Ring,
+    holes: Vec<Ring>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct RegionPolygon {
+    plant: char,
+    polygon: Polygon,
+}
+
+impl Polygon {
+    fn of(plot: &Plot) -> Self {
+        let (mut outer, holes): (Vec<Ring>, Vec<Ring>) = trace_rings(plot).into_iter().partition(|ring| signed_area(ring) > 0);
+        assert!(outer.len() <= 1, "A region has one outer boundary");
+        Polygon { outer: outer.pop().unwrap_or_default(), holes }
+    }
+
+    fn rings(&self) -> impl Iterator<Item = &Ring> {
+        std::iter::once(&self.outer).chain(self.holes.iter())
+    }
+
+    fn area(&self) -> usize {
+        self.rings().map(signed_area).sum::<i64>() as usize
+    }
+
+    fn perimeter(&self) -> usize {
+        self.rings().flat_map(ring_edges).map(|(a, b)| a.0.abs_diff(b.0) + a.1.abs_diff(b.1)).sum()
+    }
+
+    // every corner of a rectilinear ring is a turn, so there is one side per corner
+    fn sides(&self) -> usize {
+        self.rings().map(Vec::len).sum()
+    }
+}
+
+fn ring_edges(ring: &Ring) -> impl Iterator<Item = (Corner, Corner)> + '_ {
+    ring.iter().copied().zip(ring.iter().copied().cycle().skip(1))
+}
+
+// Shoelace formula: positive for the clockwise outer boundaries, negative for holes
+fn signed_area(ring: &Ring) -> i64 {
+    ring_edges(ring).map(|((x1, y1), (x2, y2))| x1 as i64 * y2 as i64 - x2 as i64 * y1 as i64).sum::<i64>() / 2
+}
+
+// Even-odd test of the cell's centre against the ring's vertical edges, in doubled coordinates
+fn ring_contains(ring: &Ring, (x, y): Cell) -> bool {
+    let (cx, cy) = (2 * x + 1, 2 * y + 1);
+    ring_edges(ring)
+        .filter(|&((x1, y1), (x2, y2))| x1 == x2 && 2 * x1 > cx && (2 * y1.min(y2)..2 * y1.max(y2)).contains(&cy))
+        .count()
+        % 2
+        == 1
+}
+
+// Walks the plot's boundary edges into closed rings, keeping the plot on the right. Where
+// two plot cells only touch at a corner the walk turns left between them, so the holes on
+// either side come out as separate rings and no ring touches itself
+fn trace_rings(plot: &Plot) -> Vec<Ring> {
+    let mut edges = Vec::new();
+    for &(x, y) in plot {
+        let (top_left, top_right, bottom_right, bottom_left) = ((x, y), (x + 1, y), (x + 1, y + 1), (x, y + 1));
+        for (dx, dy, from, to) in [(0, -1, top_left, top_right), (1, 0, top_right, bottom_right), (0, 1, bottom_right, bottom_left), (-1, 0, bottom_left, top_left)] {
+            if !contains(plot, offset((x, y), dx, dy)) {
+                edges.push((from, to));
+            }
+        }
+    }
+    edges.sort();
+    let mut outgoing: HashMap<Corner, Vec<Corner>> = HashMap::new();
+    for &(from, to) in &edges {
+        outgoing.entry(from).or_default().push(to);
+    }
+    let direction = |from: Corner, to: Corner| (to.0 as isize - from.0 as isize, to.1 as isize - from.1 as isize);
+
+    let mut used = HashSet::new();
+    let mut rings = Vec::new();
+    for &first in &edges {
+        if used.contains(&first) {
+            continue;
+        }
+        let mut points = Vec::new();
+        let (mut from, mut to) = first;
+        loop {
+            used.insert((from, to));
+            points.push(from);
+            let (dx, dy) = direction(from, to);
+            // left, straight on, then right
+            let next = [(dy, -dx), (dx, dy), (-dy, dx)]
+                .into_iter()
+                .find_map(|turn| outgoing[&to].iter().find(|&&next| direction(to, next) == turn))
+                .copied()
+                .expect("Region boundaries are closed");
+            (from, to) = (to, next);
+            if (from, to) == first {
+                break;
+            }
+        }
+        // keep only the corners where the boundary turns
+        let ring: Ring = (0..points.len())
+            .filter(|&i| {
+                let previous = points[(i + points.len() - 1) % points.len()];
+                let next = points[(i + 1) % points.len()];
+                direction(previous, points[i]) != direction(points[i], next)
+            })
+            .map(|i| points[i])
+            .collect();
+        rings.push(ring);
+    }
+    rings
+}
+
+// For each region, the index of the region immediately around it, if any
+#[derive(Debug, Clone, PartialEq)]
+struct ContainmentTree {
+    parents: Vec<Option<usize>>,
+}
+
+impl ContainmentTree {
+    // a region sits in the smallest hole of another region that contains it
+    fn of(regions: &[RegionPolygon], cells: &[Plot]) -> Self {
+        let parents = cells
+            .iter()
+            .map(|plot| {
+                let &cell = plot.iter().min()?;
+                regions
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(index, region)| region.polygon.holes.iter().map(move |hole| (index, hole)))
+                    .filter(|(_, hole)| ring_contains(hole, cell))
+                    .min_by_key(|(_, hole)| -signed_area(hole))
+                    .map(|(index, _)| index)
+            })
+            .collect();
+        ContainmentTree { parents }
+    }
+
+    fn roots(&self) -> Vec<usize> {
+        (0..self.parents.len()).filter(|&index| self.parents[index].is_none()).collect()
+    }
+
+    fn children(&self, parent: usize) -> Vec<usize> {
+        (0..self.parents.len()).filter(|&index| self.parents[index] == Some(parent)).collect()
+    }
+
+    // one region per line, indented under the region enclosing it
+    fn render(&self, regions: &[RegionPolygon]) -> String {
+        let mut result = String::new();
+        let mut stack: Vec<(usize, usize)> = self.roots().into_iter().rev().map(|index| (index, 0)).collect();
+        while let Some((index, depth)) = stack.pop() {
+            let polygon = &regions[index].polygon;
+            result.push_str(&format!("{}{} #{}: area {}, {} holes\n", "  ".repeat(depth), regions[index].plant, index, polygon.area(), polygon.holes.len()));
+            stack.extend(self.children(index).into_iter().rev().map(|child| (child, depth + 1)));
+        }
+        result
+    }
+}
+
+fn plant_colour(plant: char) -> String {
+    format!("hsl({}, 60%, 70%)", plant as u32 * 47 % 360)
+}
+
+// One path per region, holes cut out with the even-odd rule, `scale` pixels per cell
+fn to_svg(regions: &[RegionPolygon], width: usize, height: usize, scale: usize) -> String {
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {} {}\" width=\"{}\" height=\"{}\">\n",
+        width, height, width * scale, height * scale
+    );
+    for region in regions {
+        let path: Vec<String> = region
+            .polygon
+            .rings()
+            .map(|ring| {
+                let points: Vec<String> = ring.iter().map(|(x, y)| format!("{} {}", x, y)).collect();
+                format!("M{}Z", points.join(" L"))
+            })
+            .collect();
+        svg.push_str(&format!(
+            "  <path d=\"{}\" fill=\"{}\" fill-rule=\"evenodd\" stroke=\"black\" stroke-width=\"0.05\"><title>{}</title></path>\n",
+            path.join(" "),
+            plant_colour(region.plant),
+            xml_escape(region.plant)
+        ));
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn xml_escape(plant: char) -> String {
+    match plant {
+        '<' => "&lt;".to_string(),
+        '>' => "&gt;".to_string(),
+        '&' => "&amp;".to_string(),
+        _ => plant.to_string(),
     }
+}
 
+fn json_string(plant: char) -> String {
+    match plant {
+        '"' | '\\' => format!("\"\\{}\"", plant),
+        _ if plant.is_control() => format!("\"\\u{:04x}\"", plant as u32),
+        _ => format!("\"{}\"", plant),
+    }
+}
 
+// A FeatureCollection with one Polygon per region. y is negated so north is up, and the
+// rings reversed to keep the anticlockwise outer rings and clockwise holes RFC 7946 asks for
+fn to_geojson(regions: &[RegionPolygon], tree: &ContainmentTree) -> String {
+    let features: Vec<String> = regions
+        .iter()
+        .enumerate()
+        .map(|(index, region)| {
+            let rings: Vec<String> = region
+                .polygon
+                .rings()
+                .map(|ring| {
+                    let points: Vec<String> = ring.iter().rev().chain(ring.last()).map(|&(x, y)| format!("[{},{}]", x, -(y as i64))).collect();
+                    format!("[{}]", points.join(","))
+                })
+                .collect();
+            let parent = tree.parents[index].map_or("null".to_string(), |parent| parent.to_string());
+            format!(
+                "{{\"type\":\"Feature\",\"properties\":{{\"id\":{},\"plant\":{},\"area\":{},\"perimeter\":{},\"sides\":{},\"parent\":{}}},\"geometry\":{{\"type\":\"Polygon\",\"coordinates\":[{}]}}}}",
+                index,
+                json_string(region.plant),
+                region.polygon.area(),
+                region.polygon.perimeter(),
+                region.polygon.sides(),
+                parent,
+                rings.join(",")
+            )
+        })
+        .collect();
+    format!("{{\"type\":\"FeatureCollection\",\"features\":[\n{}\n]}}\n", features.join(",\n"))
 }
 
 
@@ -36,22 +663,27 @@ mod tests {
     #[test]
     fn test_load_input() {
         let farm = Farm::load_input("test_input.txt");
-
+        assert_eq!((farm.grid.width, farm.grid.height), (10, 10));
+        assert_eq!(farm.grid.vertices_len(), 100);
+        assert_eq!(farm.plant((0, 0)), Some('R'));
+        assert_eq!(farm.plant((9, 4)), Some('E'));
+        assert_eq!(farm.plant((10, 0)), None);
     }
 
     #[test]
     fn test_get_region_from_grid() {
         let farm = Farm::load_input("test_input.txt");
-
+        let region = farm.region((0, 0));
         // this should find the Rs of which there are 12
         assert_eq!(region.len(), 12);
+        assert!(region.iter().all(|&cell| farm.plant(cell) == Some('R')));
     }
 
     #[test]
     fn test_get_parimeter_for_region() {
         let farm = Farm::load_input("test_input.txt");
-        let perimeter = 0;
-        
+        let perimeter = perimeter(&farm.region((0, 0)));
+
         assert_eq!(perimeter, 18);
     }
 
@@ -59,24 +691,24 @@ mod tests {
     fn test_calculate_num_edges() {
         let grid_data = Farm::load_input("test_input.txt");
 
-        let sides = 0;
+        let sides = sides(&grid_data.region((0, 0)));
         assert_eq!(sides, 10);
     }
 
     #[test]
     fn test_get_all_regions() {
         let grid_data = Farm::load_input("test_input.txt");
-        let regions = 0;
+        let regions = grid_data.regions().len();
         assert_eq!(regions, 11);
     }
 
     #[test]
     fn can_calculate_score_for_region (){
         let grid_data = Farm::load_input("test_input.txt");
-        let region = 0;
-        let score = 0;
+        let region = grid_data.region((0, 0));
+        let score = price(&region);
         assert_eq!(score, 216);
-        let edge_score = 0;
+        let edge_score = bulk_price(&region);
         assert_eq!(edge_score, 120);
     }
 
@@ -85,16 +717,139 @@ mod tests {
     #[test]
     fn test_can_calculate_total_score() {
         let farm_data = Farm::load_input("test_input.txt");
-        let result = 0;
+        let result = farm_data.total_price();
         assert_eq!(result, 1930);
     }
 
     #[test]
     fn test_calculate_edge_score() {
         let farm_data = Farm::load_input("test_input.txt");
-        let edge_score = 0;
+        let edge_score = farm_data.total_bulk_price();
         assert_eq!(edge_score, 1206);
     }
 
+    #[test]
+    fn test_small_farms() {
+        let farm = Farm::load_pathfinding_grid("AAAA\nBBCD\nBBCC\nEEEC\n");
+        assert_eq!(farm.regions().len(), 5);
+        assert_eq!(farm.total_price(), 140);
+        assert_eq!(farm.total_bulk_price(), 80);
+
+        // the inner Xs are separate regions of the O plot, which has an outside and four inside boundaries
+        let farm = Farm::load_pathfinding_grid("OOOOO\nOXOXO\nOOOOO\nOXOXO\nOOOOO");
+        assert_eq!(farm.regions().len(), 5);
+        assert_eq!(perimeter(&farm.region((0, 0))), 36);
+        assert_eq!(farm.total_price(), 772);
+        assert_eq!(farm.total_bulk_price(), 436);
+
+        let farm = Farm::load_pathfinding_grid("AAAAAA\nAAABBA\nAAABBA\nABBAAA\nABBAAA\nAAAAAA");
+        assert_eq!(farm.total_bulk_price(), 368);
+    }
+
+    #[test]
+    fn test_region_polygons() {
+        let farm = Farm::load_input("test_input.txt");
+        let regions = farm.polygons();
+        assert_eq!(regions.len(), 11);
+        let r = &regions[0];
+        assert_eq!(r.plant, 'R');
+        assert_eq!(r.polygon.outer, vec![(0, 0), (4, 0), (4, 2), (5, 2), (5, 3), (3, 3), (3, 4), (2, 4), (2, 2), (0, 2)]);
+        assert!(r.polygon.holes.is_empty());
+        assert_eq!((r.polygon.area(), r.polygon.perimeter(), r.polygon.sides()), (12, 18, 10));
+        let plots = farm.regions();
+        for (region, plot) in regions.iter().zip(&plots) {
+            assert_eq!(region.polygon.area(), plot.len());
+        }
+
+        // B blocks touching at a corner are two separate holes in the A region
+        let farm = Farm::load_pathfinding_grid("AAAAAA\nAAABBA\nAAABBA\nABBAAA\nABBAAA\nAAAAAA");
+        let a = &farm.polygons()[0].polygon;
+        assert_eq!(a.holes, vec![vec![(1, 3), (1, 5), (3, 5), (3, 3)], vec![(3, 1), (3, 3), (5, 3), (5, 1)]]);
+        assert_eq!((a.area(), a.perimeter(), a.sides()), (28, 40, 12));
+
+        // a hole touching the outer boundary at a corner stays a hole
+        let farm = Farm::load_pathfinding_grid("AAA\nABA\nAAB");
+        let a = &farm.polygons()[0].polygon;
+        assert_eq!(a.outer, vec![(0, 0), (3, 0), (3, 2), (2, 2), (2, 3), (0, 3)]);
+        assert_eq!(a.holes, vec![vec![(1, 1), (1, 2), (2, 2), (2, 1)]]);
+        assert_eq!(a.area(), 7);
+    }
+
+    #[test]
+    fn test_containment_tree() {
+        let farm = Farm::load_pathfinding_grid("AAAAAAA\nABBBBBA\nABCBDBA\nABBBBBA\nAAAAAAA\nEEEEEEE");
+        let regions = farm.polygons();
+        let tree = ContainmentTree::of(&regions, &farm.regions());
+        assert_eq!(tree.parents, vec![None, Some(0), Some(1), Some(1), None]);
+        assert_eq!(tree.roots(), vec![0, 4]);
+        assert_eq!(tree.children(1), vec![2, 3]);
+        assert_eq!(tree.render(&regions), "A #0: area 20, 1 holes\n  B #1: area 13, 2 holes\n    C #2: area 1, 0 holes\n    D #3: area 1, 0 holes\nE #4: area 7, 0 holes\n");
+
+        let farm = Farm::load_pathfinding_grid("OOOOO\nOXOXO\nOOOOO\nOXOXO\nOOOOO");
+        let tree = ContainmentTree::of(&farm.polygons(), &farm.regions());
+        assert_eq!(tree.children(0).len(), 4);
+    }
+
+    #[test]
+    fn test_exports() {
+        let farm = Farm::load_pathfinding_grid("AAA\nABA\nAAA");
+        let regions = farm.polygons();
+        let tree = ContainmentTree::of(&regions, &farm.regions());
+        let svg = to_svg(&regions, 3, 3, 10);
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 3 3\" width=\"30\" height=\"30\">"));
+        assert!(svg.contains("d=\"M0 0 L3 0 L3 3 L0 3Z M1 1 L1 2 L2 2 L2 1Z\""));
+        assert!(svg.contains("d=\"M1 1 L2 1 L2 2 L1 2Z\""));
+        assert_eq!(svg.matches("<path").count(), 2);
 
-}
\ No newline at end of file
+        let geojson = to_geojson(&regions, &tree);
+        assert!(geojson.starts_with("{\"type\":\"FeatureCollection\",\"features\":["));
+        assert!(geojson.contains("\"properties\":{\"id\":0,\"plant\":\"A\",\"area\":8,\"perimeter\":16,\"sides\":8,\"parent\":null}"));
+        assert!(geojson.contains("\"coordinates\":[[[0,-3],[3,-3],[3,0],[0,0],[0,-3]],[[2,-1],[2,-2],[1,-2],[1,-1],[2,-1]]]"));
+        assert!(geojson.contains("\"id\":1,\"plant\":\"B\",\"area\":1,\"perimeter\":4,\"sides\":4,\"parent\":0"));
+        assert_eq!(json_string('"'), "\"\\\"\"");
+    }
+
+    #[test]
+    fn test_index_matches_full_analysis() {
+        let mut farm = Farm::load_input("test_input.txt");
+        assert_eq!(farm.prices(), Prices { price: 1930, bulk_price: 1206 });
+        assert_eq!(farm.region_stats((0, 0)), RegionStats { area: 12, perimeter: 18, sides: 10 });
+        assert_eq!(farm.index.stats.len(), 11);
+    }
+
+    #[test]
+    fn test_set_plant() {
+        // cutting a row in two, then joining it back up
+        let mut farm = Farm::load_pathfinding_grid("AAA");
+        assert_eq!(farm.set_plant((1, 0), 'B'), Prices { price: 12, bulk_price: 12 });
+        assert_eq!(farm.index.stats.len(), 3);
+        assert_eq!(farm.set_plant((1, 0), 'A'), Prices { price: 24, bulk_price: 12 });
+        assert_eq!(farm.region_stats((2, 0)), RegionStats { area: 3, perimeter: 8, sides: 4 });
+        assert_eq!(farm.set_plant((1, 0), 'A'), farm.prices());
+
+        // scattered edits, checked against a full analysis after every one
+        let mut farm = Farm::load_input("test_input.txt");
+        let mut seed = 12345usize;
+        for _ in 0..300 {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            let cell = ((seed >> 33) % 10, (seed >> 45) % 10);
+            let kind = ['R', 'I', 'C', 'E'][(seed >> 20) % 4];
+            let prices = farm.set_plant(cell, kind);
+            assert_eq!(prices, Prices { price: farm.total_price(), bulk_price: farm.total_bulk_price() });
+            assert_eq!(farm.index.stats.len(), farm.regions().len());
+            let area = farm.region(cell).len();
+            assert_eq!(farm.region_stats(cell).area, area);
+            assert!(farm.index.parent.len() <= 2 * farm.index.labels.len() + 1);
+        }
+
+        // the neighbours are apart around the cell but still joined further out
+        let mut farm = Farm::load_pathfinding_grid("AAAAA\nABABA\nAAAAA");
+        assert_eq!(farm.ring_groups((2, 0), 'A', &[(1, 0), (3, 0), (2, 1)]).len(), 3);
+        let block = Farm::load_pathfinding_grid("AAA\nAAA\nAAA");
+        assert_eq!(block.ring_groups((1, 1), 'A', &[(1, 0), (2, 1), (1, 2), (0, 1)]), vec![(1, 0)]);
+        farm.set_plant((2, 0), 'C');
+        assert_eq!(farm.region_stats((0, 0)).area, 12);
+        assert_eq!(farm.prices(), Prices { price: farm.total_price(), bulk_price: farm.total_bulk_price() });
+        assert_eq!(farm.index.stats.len(), 4);
+    }
+}
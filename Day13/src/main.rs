@@ -1,20 +1,38 @@
 use regex::Regex;
 
 fn main() {
-    let claw_machines = ClawMachine::from_file("input.txt");
-
-    let total_minimum_cost: u128 = claw_machines.iter()
-        .map(|machine| machine.calculate_minimum_cost().unwrap_or(0))
-        .sum();
-
-    println!("The smallest cost to win is: {}", total_minimum_cost);
-
+    let input = std::fs::read_to_string("input.txt").expect("Failed to read the file");
+    let machines = parse_machines(&input);
+    let unreadable = machines.iter().filter(|machine| machine.is_err()).count();
     let large_offset: i128 = 10_000_000_000_000;
-    let total_minimum_cost_with_offset: u128 = claw_machines.iter()
-        .map(|machine| machine.calculate_minimum_cost_with_offset(large_offset).unwrap_or(0))
-        .sum();
+    let part_1 = reports(&machines, 0, Some(100));
+    let part_2 = reports(&machines, large_offset, None);
+    for (label, reports) in [("The smallest cost to win is", &part_1), ("The smallest cost to win with added offset is", &part_2)] {
+        println!("{}: {} ({} of {} machines can't be won, {} of them unreadable)",
+            label, reports.iter().filter_map(|report| report.cost).sum::<u128>(),
+            reports.iter().filter(|report| report.cost.is_none()).count(), reports.len(), unreadable);
+    }
+
+    // --report=json|table lists every machine in the file, including any that don't parse,
+    // for part 1 or with --part=2 for the offset prizes
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(format) = args.iter().find_map(|arg| arg.strip_prefix("--report=")) {
+        let reports = if args.iter().any(|arg| arg == "--part=2") { &part_2 } else { &part_1 };
+        match format {
+            "json" => print!("{}", reports_to_json(reports)),
+            _ => print!("{}", reports_to_table(reports)),
+        }
+    }
 
-    println!("The smallest cost to win with added offset is: {}", total_minimum_cost_with_offset);
+    // --closed-form re-solves the two-button machines with the original two-button solver
+    if args.iter().any(|arg| arg == "--closed-form") {
+        let claw_machines: Vec<ClawMachine> = machines.iter()
+            .filter_map(|machine| machine.as_ref().ok()?.to_claw_machine())
+            .collect();
+        let part_1: u128 = claw_machines.iter().filter_map(|machine| machine.calculate_minimum_cost()).sum();
+        let part_2: u128 = claw_machines.iter().filter_map(|machine| machine.calculate_minimum_cost_with_offset(large_offset)).sum();
+        println!("Two-button solver: {} and {} over {} machines", part_1, part_2, claw_machines.len());
+    }
 }
 
 type Offset = (i128, i128);
@@ -26,6 +44,7 @@ struct ClawMachine {
 }
 
 impl ClawMachine {
+    #[cfg(test)]
     fn new() -> ClawMachine {
         ClawMachine {
             button_a: (0, 0),
@@ -34,41 +53,17 @@ impl ClawMachine {
         }
     }
 
+    // The first machine in `data`, if it has two buttons. Costs stay at 3 and 1
+    #[cfg(test)]
     fn from_serialised(data: &str) -> Option<ClawMachine> {
-        let re = Regex::new(r"Button A: X\+(\d+), Y\+(\d+)\nButton B: X\+(\d+), Y\+(\d+)\nPrize: X=(\d+), Y=(\d+)").unwrap();
-        if let Some(cap) = re.captures(data) {
-            let button_a_x: i128 = cap[1].parse().ok()?;
-            let button_a_y: i128 = cap[2].parse().ok()?;
-            let button_b_x: i128 = cap[3].parse().ok()?;
-            let button_b_y: i128 = cap[4].parse().ok()?;
-            let prize_x: i128 = cap[5].parse().ok()?;
-            let prize_y: i128 = cap[6].parse().ok()?;
-
-            Some(ClawMachine {
-                button_a: (button_a_x, button_a_y),
-                button_b: (button_b_x, button_b_y),
-                prize: (prize_x, prize_y),
-            })
-        } else {
-            None
-        }
+        parse_machines(data).into_iter().next()?.ok()?.to_claw_machine()
     }
 
+    // The two-button machines in a file; `reports` accounts for the rest
+    #[cfg(test)]
     fn from_file(file: &str) -> Vec<ClawMachine> {
         let data = std::fs::read_to_string(file).expect("Failed to read the file");
-        let mut claw_machines = Vec::new();
-        let normalized_data = data.replace("\r\n", "\n");
-
-        let blocks = normalized_data.split("\n\n");
-        for block in blocks {
-            if let Some(machine) = ClawMachine::from_serialised(block) {
-                claw_machines.push(machine);
-            } else {
-                eprintln!("Failed to parse data block:\n{}", block);
-            }
-        }
-
-        claw_machines
+        parse_machines(&data).into_iter().filter_map(|machine| machine.ok()?.to_claw_machine()).collect()
     }
 
     fn calculate_minimum_cost(&self) -> Option<u128>{
@@ -82,7 +77,7 @@ impl ClawMachine {
     fn calculate_minimum_cost_i(&self, prize_x: i128, prize_y: i128, press_limit: Option<i128>) -> Option<u128> {
         let determinant = self.button_a.0 * self.button_b.1 - self.button_a.1 * self.button_b.0;
         if determinant == 0 {
-            return None;
+            return self.calculate_collinear_cost(prize_x, prize_y, press_limit);
         }
 
         let numerator_a = prize_x * self.button_b.1 - prize_y * self.button_b.0;
@@ -111,6 +106,538 @@ impl ClawMachine {
 
         Some((press_a * 3 + press_b) as u128)
     }
+
+    // Buttons along one line can only reach prizes on it, and there the presses solve
+    // a * n_a + b * n_b = prize along one axis. Extended Euclid gives every solution as
+    // n_a = n_a0 + k * b / g, n_b = n_b0 - k * a / g, and the cost is linear in k, so the
+    // cheapest is at one end of the range of k the press limits allow
+    fn calculate_collinear_cost(&self, prize_x: i128, prize_y: i128, press_limit: Option<i128>) -> Option<u128> {
+        let prize = (prize_x, prize_y);
+        if cross(self.button_a, prize) != 0 || cross(self.button_b, prize) != 0 {
+            return None;
+        }
+        let ((a, b), target) = if self.button_a.0 != 0 || self.button_b.0 != 0 {
+            ((self.button_a.0, self.button_b.0), prize_x)
+        } else if self.button_a.1 != 0 || self.button_b.1 != 0 {
+            ((self.button_a.1, self.button_b.1), prize_y)
+        } else {
+            // neither button moves the claw
+            return (prize == (0, 0)).then_some(0);
+        };
+        let (g, x, y) = extended_gcd(a, b);
+        if target % g != 0 {
+            return None;
+        }
+        let (press_a, press_b) = (x * (target / g), y * (target / g));
+        let (step_a, step_b) = (b / g, -(a / g));
+
+        // the range of k keeping presses + k * step within 0..=press_limit
+        let mut low: Option<i128> = None;
+        let mut high: Option<i128> = None;
+        for (presses, step) in [(press_a, step_a), (press_b, step_b)] {
+            let bounds = [Some(-presses), press_limit.map(|limit| limit - presses)];
+            let (lower, upper) = match step.signum() {
+                0 if presses < 0 || press_limit.is_some_and(|limit| presses > limit) => return None,
+                0 => continue,
+                1 => (bounds[0].map(|bound| ceil_div(bound, step)), bounds[1].map(|bound| floor_div(bound, step))),
+                _ => (bounds[1].map(|bound| ceil_div(bound, step)), bounds[0].map(|bound| floor_div(bound, step))),
+            };
+            low = low.max(lower);
+            high = match (high, upper) {
+                (Some(high), Some(upper)) => Some(high.min(upper)),
+                (high, upper) => high.or(upper),
+            };
+        }
+        if let (Some(low), Some(high)) = (low, high) {
+            if low > high {
+                return None;
+            }
+        }
+        [low, high]
+            .into_iter()
+            .flatten()
+            .map(|k| ((press_a + k * step_a) * 3 + press_b + k * step_b) as u128)
+            .min()
+    }
+
+    // The same machine for the general solver: A costs 3 and B costs 1
+    #[cfg(test)]
+    fn to_machine(&self, press_limit: Option<i128>) -> Machine {
+        Machine {
+            buttons: vec![
+                Button { name: "A".to_string(), offset: self.button_a, cost: 3, press_limit },
+                Button { name: "B".to_string(), offset: self.button_b, cost: 1, press_limit },
+            ],
+            prize: self.prize,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Button {
+    name: String,
+    offset: Offset,
+    cost: u128,
+    press_limit: Option<i128>,
+}
+
+// A claw machine with any number of buttons
+#[derive(Debug, Clone, PartialEq)]
+struct Machine {
+    buttons: Vec<Button>,
+    prize: Offset,
+}
+
+// Presses of each button, in the machine's button order, and what they cost
+#[derive(Debug, Clone, PartialEq)]
+struct Solution {
+    presses: Vec<i128>,
+    cost: u128,
+}
+
+// (constant + slope * n) / divisor, with the divisor kept positive
+#[derive(Debug, Clone, Copy)]
+struct Affine {
+    constant: i128,
+    slope: i128,
+    divisor: i128,
+}
+
+impl Affine {
+    fn new(constant: i128, slope: i128, divisor: i128) -> Self {
+        let sign = divisor.signum();
+        Affine { constant: constant * sign, slope: slope * sign, divisor: divisor * sign }
+    }
+
+    fn at(&self, n: i128) -> i128 {
+        (self.constant + self.slope * n) / self.divisor
+    }
+
+    // the n ≡ r (mod m) for which the value is a whole number
+    fn whole_values(&self) -> Option<(i128, i128)> {
+        solve_congruence(self.slope, -self.constant, self.divisor)
+    }
+}
+
+fn cross(a: Offset, b: Offset) -> i128 {
+    a.0 * b.1 - a.1 * b.0
+}
+
+// (g, x, y) with a * x + b * y = g = gcd(a, b) >= 0
+fn extended_gcd(a: i128, b: i128) -> (i128, i128, i128) {
+    if b == 0 {
+        (a.abs(), a.signum(), 0)
+    } else {
+        let (g, x, y) = extended_gcd(b, a.rem_euclid(b));
+        (g, y, x - a.div_euclid(b) * y)
+    }
+}
+
+// a * n ≡ b (mod m) as n ≡ r (mod modulus), for m > 0
+fn solve_congruence(a: i128, b: i128, m: i128) -> Option<(i128, i128)> {
+    let (g, x, _) = extended_gcd(a.rem_euclid(m), m);
+    if b.rem_euclid(g) != 0 {
+        return None;
+    }
+    let modulus = m / g;
+    Some(((b / g).rem_euclid(modulus) * x.rem_euclid(modulus) % modulus, modulus))
+}
+
+// Chinese remainder theorem for moduli that need not be coprime
+fn combine_congruences((r1, m1): (i128, i128), (r2, m2): (i128, i128)) -> Option<(i128, i128)> {
+    let (g, x, _) = extended_gcd(m1, m2);
+    if (r2 - r1).rem_euclid(g) != 0 {
+        return None;
+    }
+    let modulus = m1 / g * m2;
+    let step = ((r2 - r1) / g).rem_euclid(m2 / g) * x.rem_euclid(m2 / g) % (m2 / g);
+    Some(((r1 + m1 * step).rem_euclid(modulus), modulus))
+}
+
+impl Machine {
+    fn to_claw_machine(&self) -> Option<ClawMachine> {
+        match &self.buttons[..] {
+            [a, b] => Some(ClawMachine { button_a: a.offset, button_b: b.offset, prize: self.prize }),
+            _ => None,
+        }
+    }
+
+    fn cost(&self, presses: &[i128]) -> u128 {
+        self.buttons.iter().zip(presses).map(|(button, &n)| button.cost * n as u128).sum()
+    }
+
+    // The cheapest way to win, exact for any number of buttons. With three buttons or fewer
+    // it is closed form; beyond that all but three are searched within their press limits
+    fn solve(&self) -> Result<Solution, String> {
+        // buttons that don't move the claw are never worth pressing
+        let active: Vec<usize> = (0..self.buttons.len()).filter(|&i| self.buttons[i].offset != (0, 0)).collect();
+        let mut budget = SEARCH_BUDGET;
+        self.solve_among(&active, self.prize, &mut vec![0; self.buttons.len()], &mut budget)
+            .ok_or_else(|| "No combination of presses reaches the prize".to_string())?
+    }
+
+    // Err when a search is unbounded, Ok(None) when there is no solution. `presses` holds the
+    // presses already chosen for the buttons outside `active`, and `budget` the press counts
+    // searches may still try
+    fn solve_among(&self, active: &[usize], target: Offset, presses: &mut Vec<i128>, budget: &mut u128) -> Option<Result<Solution, String>> {
+        let pair = active.iter().enumerate()
+            .flat_map(|(k, &i)| active[k + 1..].iter().map(move |&j| (i, j)))
+            .find(|&(i, j)| cross(self.buttons[i].offset, self.buttons[j].offset) != 0);
+        let free: Vec<usize> = active.iter().copied().filter(|&i| pair.is_none_or(|(a, b)| i != a && i != b)).collect();
+        let solution = match (pair, free.len()) {
+            (_, 0) if active.is_empty() => (target == (0, 0)).then(|| self.finish(presses)),
+            (None, 1) => self.solve_single(free[0], target, presses),
+            (None, 2) => self.solve_collinear(free[0], free[1], target, presses),
+            (Some((a, b)), 0) => self.solve_pair(a, b, target, presses),
+            (Some((a, b)), 1) => self.solve_on_line(free[0], a, b, target, presses),
+            _ => return self.search(free[0], active, target, presses, budget),
+        };
+        solution.map(Ok)
+    }
+
+    fn finish(&self, presses: &[i128]) -> Solution {
+        Solution { presses: presses.to_vec(), cost: self.cost(presses) }
+    }
+
+    fn within_limit(&self, button: usize, n: i128) -> bool {
+        n >= 0 && self.buttons[button].press_limit.is_none_or(|limit| n <= limit)
+    }
+
+    fn solve_single(&self, button: usize, target: Offset, presses: &mut [i128]) -> Option<Solution> {
+        let offset = self.buttons[button].offset;
+        let n = if offset.0 != 0 { target.0 / offset.0 } else { target.1 / offset.1 };
+        if (offset.0 * n, offset.1 * n) != target || !self.within_limit(button, n) {
+            return None;
+        }
+        presses[button] = n;
+        let solution = self.finish(presses);
+        presses[button] = 0;
+        Some(solution)
+    }
+
+    // Cramer's rule for two buttons that move in different directions
+    fn solve_pair(&self, a: usize, b: usize, target: Offset, presses: &mut [i128]) -> Option<Solution> {
+        let (offset_a, offset_b) = (self.buttons[a].offset, self.buttons[b].offset);
+        let determinant = cross(offset_a, offset_b);
+        let (numerator_a, numerator_b) = (cross(target, offset_b), cross(offset_a, target));
+        if numerator_a % determinant != 0 || numerator_b % determinant != 0 {
+            return None;
+        }
+        let (n_a, n_b) = (numerator_a / determinant, numerator_b / determinant);
+        if !self.within_limit(a, n_a) || !self.within_limit(b, n_b) {
+            return None;
+        }
+        presses[a] = n_a;
+        presses[b] = n_b;
+        let solution = self.finish(presses);
+        presses[a] = 0;
+        presses[b] = 0;
+        Some(solution)
+    }
+
+    // Two buttons along the same line: a one-dimensional Diophantine equation in the
+    // presses of `a`, with `b`'s presses following from them
+    fn solve_collinear(&self, a: usize, b: usize, target: Offset, presses: &mut [i128]) -> Option<Solution> {
+        let (offset_a, offset_b) = (self.buttons[a].offset, self.buttons[b].offset);
+        if cross(offset_a, target) != 0 {
+            return None;
+        }
+        // any axis the line isn't perpendicular to
+        let (step_a, step_b, goal) = if offset_a.0 != 0 || offset_b.0 != 0 {
+            (offset_a.0, offset_b.0, target.0)
+        } else {
+            (offset_a.1, offset_b.1, target.1)
+        };
+        let presses_b = Affine::new(goal, -step_a, step_b);
+        let progression = presses_b.whole_values()?;
+        self.cheapest_on_line(a, &[(b, presses_b)], progression, presses)
+    }
+
+    // One button beyond an independent pair: the pair's presses are affine in the free
+    // button's, which must make both whole numbers
+    fn solve_on_line(&self, free: usize, a: usize, b: usize, target: Offset, presses: &mut [i128]) -> Option<Solution> {
+        let (offset_a, offset_b, offset_free) = (self.buttons[a].offset, self.buttons[b].offset, self.buttons[free].offset);
+        let determinant = cross(offset_a, offset_b);
+        let presses_a = Affine::new(cross(target, offset_b), -cross(offset_free, offset_b), determinant);
+        let presses_b = Affine::new(cross(offset_a, target), -cross(offset_a, offset_free), determinant);
+        let progression = combine_congruences(presses_a.whole_values()?, presses_b.whole_values()?)?;
+        self.cheapest_on_line(free, &[(a, presses_a), (b, presses_b)], progression, presses)
+    }
+
+    // Presses n ≡ r (mod m) of `button` fixing the others through `dependents`. The cost is
+    // linear in n, so the cheapest is at one end of the range every limit allows
+    fn cheapest_on_line(&self, button: usize, dependents: &[(usize, Affine)], (r, m): (i128, i128), presses: &mut [i128]) -> Option<Solution> {
+        let mut low = 0;
+        let mut high = self.buttons[button].press_limit;
+        for &(dependent, affine) in dependents {
+            // 0 <= constant + slope * n <= limit * divisor
+            let upper = self.buttons[dependent].press_limit.map(|limit| limit * affine.divisor - affine.constant);
+            let lower = -affine.constant;
+            match affine.slope.signum() {
+                0 => {
+                    if lower > 0 || upper.is_some_and(|upper| upper < 0) {
+                        return None;
+                    }
+                }
+                1 => {
+                    low = low.max(ceil_div(lower, affine.slope));
+                    if let Some(upper) = upper {
+                        high = Some(high.map_or(floor_div(upper, affine.slope), |high| high.min(floor_div(upper, affine.slope))));
+                    }
+                }
+                _ => {
+                    high = Some(high.map_or(floor_div(lower, affine.slope), |high| high.min(floor_div(lower, affine.slope))));
+                    if let Some(upper) = upper {
+                        low = low.max(ceil_div(upper, affine.slope));
+                    }
+                }
+            }
+        }
+        let first = low + (r - low).rem_euclid(m);
+        let mut candidates = vec![first];
+        if let Some(high) = high {
+            candidates.push(high - (high - r).rem_euclid(m));
+        }
+        candidates
+            .into_iter()
+            .filter(|&n| high.is_none_or(|high| n <= high) && n >= low)
+            .map(|n| {
+                presses[button] = n;
+                for &(dependent, affine) in dependents {
+                    presses[dependent] = affine.at(n);
+                }
+                let solution = self.finish(presses);
+                presses[button] = 0;
+                for &(dependent, _) in dependents {
+                    presses[dependent] = 0;
+                }
+                solution
+            })
+            .min_by_key(|solution| solution.cost)
+    }
+
+    // Tries every press count of one button, which needs a limit on it: its own, or one
+    // implied by the prize when no other button can move back along some axis. The counts
+    // tried, nested searches included, have to fit in the budget
+    fn search(&self, button: usize, active: &[usize], target: Offset, presses: &mut Vec<i128>, budget: &mut u128) -> Option<Result<Solution, String>> {
+        let offset = self.buttons[button].offset;
+        let implied = [(offset.0, target.0, 0), (offset.1, target.1, 1)]
+            .into_iter()
+            .filter(|&(step, goal, axis)| {
+                let same_way = |value: i128| value.signum() * step.signum() >= 0;
+                step != 0 && same_way(goal) && active.iter().all(|&i| same_way(if axis == 0 { self.buttons[i].offset.0 } else { self.buttons[i].offset.1 }))
+            })
+            .map(|(step, goal, _)| goal / step)
+            .min();
+        let Some(limit) = self.buttons[button].press_limit.into_iter().chain(implied).min() else {
+            return Some(Err(format!("Button {} needs a press limit to search", self.buttons[button].name)));
+        };
+        let too_large = || {
+            Some(Err(format!(
+                "Button {} needs up to {} presses tried, over the search budget of {}: unbounded search",
+                self.buttons[button].name, limit + 1, SEARCH_BUDGET
+            )))
+        };
+        if limit >= 0 && limit as u128 >= *budget {
+            return too_large();
+        }
+        let rest: Vec<usize> = active.iter().copied().filter(|&i| i != button).collect();
+        let mut best: Option<Solution> = None;
+        for n in 0..=limit {
+            // a nested search may have used up what was left
+            let Some(left) = budget.checked_sub(1) else {
+                presses[button] = 0;
+                return too_large();
+            };
+            *budget = left;
+            presses[button] = n;
+            match self.solve_among(&rest, (target.0 - offset.0 * n, target.1 - offset.1 * n), presses, budget) {
+                Some(Err(error)) => {
+                    presses[button] = 0;
+                    return Some(Err(error));
+                }
+                Some(Ok(solution)) if best.as_ref().is_none_or(|best| solution.cost < best.cost) => best = Some(solution),
+                _ => {}
+            }
+        }
+        presses[button] = 0;
+        best.map(Ok)
+    }
+}
+
+// Press counts a machine's search may try before giving up on it
+const SEARCH_BUDGET: u128 = 1_000_000;
+
+fn floor_div(a: i128, b: i128) -> i128 {
+    let quotient = a / b;
+    if a % b != 0 && (a < 0) != (b < 0) { quotient - 1 } else { quotient }
+}
+
+fn ceil_div(a: i128, b: i128) -> i128 {
+    -floor_div(-a, b)
+}
+
+// Splits "X+94, Y=-3, cost 2" into its named numbers, lowercased, signs and spacing optional
+fn parse_fields(text: &str, allowed: &[&str]) -> Result<Vec<(String, i128)>, String> {
+    let field = Regex::new(r"(?i)^\s*([a-z]+)\s*[=:]?\s*([+-]?)\s*(\d+)\s*$").unwrap();
+    let mut fields: Vec<(String, i128)> = Vec::new();
+    for part in text.split(',') {
+        let captures = field.captures(part).ok_or_else(|| format!("Can't read '{}'", part.trim()))?;
+        let name = captures[1].to_lowercase();
+        if !allowed.contains(&name.as_str()) {
+            return Err(format!("Unknown field '{}'", &captures[1]));
+        }
+        if fields.iter().any(|(seen, _)| *seen == name) {
+            return Err(format!("'{}' is given twice", &captures[1]));
+        }
+        let value: i128 = captures[3].parse().map_err(|e| format!("Bad number '{}': {}", &captures[3], e))?;
+        fields.push((name, if &captures[2] == "-" { -value } else { value }));
+    }
+    Ok(fields)
+}
+
+fn field(fields: &[(String, i128)], name: &str) -> Option<i128> {
+    fields.iter().find(|(field, _)| field == name).map(|&(_, value)| value)
+}
+
+fn required_field(fields: &[(String, i128)], name: &str) -> Result<i128, String> {
+    field(fields, name).ok_or_else(|| format!("Missing {}", name.to_uppercase()))
+}
+
+// Machines as `Button <name>: X+a, Y+b[, cost c][, limit n]` lines closed by a
+// `Prize: X=x, Y=y` line, case and spacing free, blank lines optional. Button A costs 3
+// and any other button 1 unless given a cost. A machine with a line that can't be read
+// comes back as the reason, so it can be reported rather than lost
+fn parse_machines(input: &str) -> Vec<Result<Machine, String>> {
+    let button = Regex::new(r"(?i)^button\s+(\w+)\s*:(.*)$").unwrap();
+    let prize = Regex::new(r"(?i)^prize\s*:(.*)$").unwrap();
+    let mut machines = Vec::new();
+    let mut buttons: Vec<Button> = Vec::new();
+    let mut error: Option<String> = None;
+    for (number, line) in input.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let context = |reason: String| format!("Line {}: {}", number + 1, reason);
+        if let Some(captures) = button.captures(line) {
+            let name = captures[1].to_string();
+            let parsed = parse_fields(&captures[2], &["x", "y", "cost", "limit"]).and_then(|fields| {
+                let cost = field(&fields, "cost").unwrap_or(if name.eq_ignore_ascii_case("a") { 3 } else { 1 });
+                Ok(Button {
+                    offset: (required_field(&fields, "x")?, required_field(&fields, "y")?),
+                    cost: u128::try_from(cost).map_err(|_| "Costs can't be negative".to_string())?,
+                    press_limit: field(&fields, "limit"),
+                    name,
+                })
+            });
+            match parsed {
+                Ok(parsed) if buttons.iter().any(|button| button.name == parsed.name) => {
+                    error.get_or_insert(context(format!("Button {} is given twice", parsed.name)));
+                }
+                Ok(parsed) => buttons.push(parsed),
+                Err(reason) => {
+                    error.get_or_insert(context(reason));
+                }
+            }
+        } else if let Some(captures) = prize.captures(line) {
+            let parsed = parse_fields(&captures[1], &["x", "y"])
+                .and_then(|fields| Ok((required_field(&fields, "x")?, required_field(&fields, "y")?)))
+                .map_err(context);
+            let machine = match (error.take(), parsed) {
+                (Some(reason), _) | (None, Err(reason)) => Err(reason),
+                (None, Ok(prize)) => Ok(Machine { buttons: std::mem::take(&mut buttons), prize }),
+            };
+            buttons.clear();
+            machines.push(machine);
+        } else {
+            error.get_or_insert(context(format!("Expected a button or a prize, not '{}'", line)));
+        }
+    }
+    if !buttons.is_empty() || error.is_some() {
+        machines.push(Err(error.unwrap_or_else(|| "Machine has no prize".to_string())));
+    }
+    machines
+}
+
+// How one machine in a file went
+#[derive(Debug, Clone, PartialEq)]
+struct MachineReport {
+    machine: usize,
+    presses: Vec<(String, i128)>,
+    cost: Option<u128>,
+    reason: Option<String>,
+}
+
+// Each machine solved with `offset` added to its prize, and `press_limit` for buttons without one
+fn reports(machines: &[Result<Machine, String>], offset: i128, press_limit: Option<i128>) -> Vec<MachineReport> {
+    machines
+        .iter()
+        .enumerate()
+        .map(|(index, machine)| {
+            let solved = machine.clone().and_then(|mut machine| {
+                machine.prize = (machine.prize.0 + offset, machine.prize.1 + offset);
+                for button in &mut machine.buttons {
+                    button.press_limit = button.press_limit.or(press_limit);
+                }
+                machine.solve().map(|solution| (machine, solution))
+            });
+            match solved {
+                Ok((machine, solution)) => MachineReport {
+                    machine: index + 1,
+                    presses: machine.buttons.iter().map(|button| button.name.clone()).zip(solution.presses).collect(),
+                    cost: Some(solution.cost),
+                    reason: None,
+                },
+                Err(reason) => MachineReport { machine: index + 1, presses: Vec::new(), cost: None, reason: Some(reason) },
+            }
+        })
+        .collect()
+}
+
+fn json_escape(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '"' => "\\\"".to_string(),
+            '\\' => "\\\\".to_string(),
+            c if c.is_control() => format!("\\u{:04x}", c as u32),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+fn reports_to_json(reports: &[MachineReport]) -> String {
+    let lines: Vec<String> = reports
+        .iter()
+        .map(|report| match (&report.cost, &report.reason) {
+            (Some(cost), _) => {
+                let presses: Vec<String> = report.presses.iter().map(|(name, n)| format!("\"{}\":{}", json_escape(name), n)).collect();
+                format!("{{\"machine\":{},\"solvable\":true,\"presses\":{{{}}},\"cost\":{}}}", report.machine, presses.join(","), cost)
+            }
+            (None, reason) => format!(
+                "{{\"machine\":{},\"solvable\":false,\"reason\":\"{}\"}}",
+                report.machine,
+                json_escape(reason.as_deref().unwrap_or_default())
+            ),
+        })
+        .collect();
+    format!("[\n{}\n]\n", lines.join(",\n"))
+}
+
+fn reports_to_table(reports: &[MachineReport]) -> String {
+    let mut table = format!("{:<8} {:<8} {:<24} {:>16}  {}\n", "machine", "solvable", "presses", "cost", "reason");
+    for report in reports {
+        let presses: Vec<String> = report.presses.iter().map(|(name, n)| format!("{}={}", name, n)).collect();
+        table.push_str(&format!(
+            "{:<8} {:<8} {:<24} {:>16}  {}\n",
+            report.machine,
+            if report.cost.is_some() { "yes" } else { "no" },
+            presses.join(" "),
+            report.cost.map_or(String::new(), |cost| cost.to_string()),
+            report.reason.as_deref().unwrap_or_default()
+        ));
+    }
+    table
 }
 
 #[cfg(test)]
@@ -176,4 +703,238 @@ mod tests {
         let cost = machine.calculate_minimum_cost_with_offset(offset);
         assert_eq!(cost, None);
     }
+
+    // every combination within the limits, for checking the solver on small machines
+    fn brute_force(machine: &Machine) -> Option<u128> {
+        let mut best = None;
+        let mut presses = vec![0; machine.buttons.len()];
+        loop {
+            let position = machine.buttons.iter().zip(&presses)
+                .fold((0, 0), |(x, y), (button, &n)| (x + button.offset.0 * n, y + button.offset.1 * n));
+            if position == machine.prize {
+                let cost = machine.cost(&presses);
+                best = Some(best.map_or(cost, |best: u128| best.min(cost)));
+            }
+            let Some(i) = (0..presses.len()).find(|&i| presses[i] < machine.buttons[i].press_limit.unwrap()) else {
+                return best;
+            };
+            presses[i] += 1;
+            presses[..i].iter_mut().for_each(|n| *n = 0);
+        }
+    }
+
+    fn button(name: &str, offset: Offset, cost: u128, press_limit: Option<i128>) -> Button {
+        Button { name: name.to_string(), offset, cost, press_limit }
+    }
+
+    #[test]
+    fn test_general_solver_matches_two_buttons() {
+        let machines = ClawMachine::from_file("test_input.txt");
+        for machine in &machines {
+            let solution = machine.to_machine(Some(100)).solve().ok();
+            assert_eq!(solution.map(|solution| solution.cost), machine.calculate_minimum_cost());
+        }
+        let solution = machines[0].to_machine(Some(100)).solve().unwrap();
+        assert_eq!(solution, Solution { presses: vec![80, 40], cost: 280 });
+        let total: u128 = machines.iter()
+            .filter_map(|machine| {
+                let mut general = machine.to_machine(None);
+                general.prize = (general.prize.0 + 10_000_000_000_000, general.prize.1 + 10_000_000_000_000);
+                general.solve().ok()
+            })
+            .map(|solution| solution.cost)
+            .sum();
+        assert_eq!(total, 875318608908);
+    }
+
+    #[test]
+    fn test_three_and_four_buttons() {
+        // the diagonal button is cheapest, even for an enormous prize
+        let machine = Machine {
+            buttons: vec![button("X", (1, 0), 1, None), button("Y", (0, 1), 1, None), button("D", (1, 1), 1, None)],
+            prize: (10_000_000_000_000, 10_000_000_000_007),
+        };
+        assert_eq!(machine.solve().unwrap().presses, vec![0, 7, 10_000_000_000_000]);
+
+        let mut seed = 7u64;
+        let mut next = |bound: u64| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            ((seed >> 33) % bound) as i128
+        };
+        let mut solved = 0;
+        for round in 0..300 {
+            let buttons: Vec<Button> = (0..3 + round % 2)
+                .map(|i| button(&i.to_string(), (next(7), next(7)), 1 + next(5) as u128, Some(3 + next(8))))
+                .collect();
+            let presses: Vec<i128> = buttons.iter().map(|button| next(button.press_limit.unwrap() as u64 + 1)).collect();
+            let prize = buttons.iter().zip(&presses)
+                .fold((0, 0), |(x, y), (button, &n)| (x + button.offset.0 * n, y + button.offset.1 * n));
+            let mut machine = Machine { buttons, prize };
+            if round % 5 == 0 {
+                machine.prize.0 += 1;
+            }
+            let expected = brute_force(&machine);
+            assert_eq!(machine.solve().ok().map(|solution| solution.cost), expected, "{:?}", machine);
+            if let Ok(solution) = machine.solve() {
+                assert_eq!(machine.cost(&solution.presses), solution.cost);
+                solved += 1;
+            }
+        }
+        assert!(solved > 200);
+    }
+
+    #[test]
+    fn test_unbounded_search() {
+        let machine = Machine {
+            buttons: vec![
+                button("L", (-1, 0), 1, None), button("R", (1, 0), 1, None),
+                button("U", (0, -1), 1, None), button("D", (0, 1), 1, None),
+            ],
+            prize: (2, 3),
+        };
+        assert_eq!(machine.solve(), Err("Button R needs a press limit to search".to_string()));
+        let mut limited = machine.clone();
+        limited.buttons[1].press_limit = Some(5);
+        assert_eq!(limited.solve().unwrap().presses, vec![0, 2, 0, 3]);
+        // a prize this far off implies a limit, but far too many presses to try
+        let far = Machine {
+            buttons: vec![
+                button("A", (3, 1), 3, None), button("B", (1, 3), 1, None),
+                button("C", (2, 1), 2, None), button("D", (1, 2), 2, None),
+            ],
+            prize: (10_000_000_000_000, 10_000_000_000_000),
+        };
+        assert!(far.solve().unwrap_err().ends_with("unbounded search"));
+        // nested searches share the budget
+        let mut nested = far.clone();
+        nested.buttons.push(button("E", (1, 1), 1, Some(2_000)));
+        nested.buttons[2].press_limit = Some(2_000);
+        nested.prize = (10_000, 10_000);
+        assert!(nested.solve().unwrap_err().ends_with("unbounded search"));
+        // an inner search can spend the last of the budget before the outer one asks for more
+        let drained = Machine {
+            buttons: vec![
+                button("A", (1, 1), 1, Some(1)), button("B", (2, 2), 1, Some(999_998)),
+                button("C", (3, 3), 1, None), button("D", (5, 5), 1, None),
+            ],
+            prize: (2_000_000, 2_000_000),
+        };
+        assert!(drained.solve().unwrap_err().ends_with("unbounded search"));
+        // with no presses at all there is nothing to search
+        assert_eq!(Machine { buttons: Vec::new(), prize: (0, 0) }.solve().unwrap().cost, 0);
+        assert!(Machine { buttons: Vec::new(), prize: (1, 0) }.solve().is_err());
+    }
+
+    #[test]
+    fn test_parallel_buttons() {
+        let machine = |a: Offset, b: Offset, prize: Offset| ClawMachine { button_a: a, button_b: b, prize };
+        // 2a + 3b = 7 only as a = 2, b = 1
+        assert_eq!(machine((2, 2), (3, 3), (7, 7)).calculate_minimum_cost(), Some(7));
+        // B alone is cheapest per step
+        assert_eq!(machine((2, 2), (3, 3), (12, 12)).calculate_minimum_cost(), Some(4));
+        // A covers four steps for 3 tokens, so as many As as will fit
+        assert_eq!(machine((4, 4), (1, 1), (10, 10)).calculate_minimum_cost(), Some(8));
+        // off the line the buttons move along
+        assert_eq!(machine((2, 2), (3, 3), (7, 8)).calculate_minimum_cost(), None);
+        // on the line but not a multiple of gcd(4, 6)
+        assert_eq!(machine((4, 2), (6, 3), (9, 9)).calculate_minimum_cost(), None);
+        assert_eq!(machine((4, 2), (6, 3), (10, 5)).calculate_minimum_cost(), Some(4));
+    }
+
+    #[test]
+    fn test_collinear_prizes_within_limits() {
+        let machine = |a: Offset, b: Offset, prize: Offset| ClawMachine { button_a: a, button_b: b, prize };
+        assert_eq!(machine((1, 2), (3, 6), (300, 600)).calculate_minimum_cost(), Some(100));
+        assert_eq!(machine((1, 2), (3, 6), (301, 602)).calculate_minimum_cost(), Some(103));
+        // B alone needs 150 presses, and no mix stays within 100 of each
+        assert_eq!(machine((1, 2), (2, 4), (450, 900)).calculate_minimum_cost(), None);
+        // vertical buttons are solved along y
+        assert_eq!(machine((0, 5), (0, 2), (0, 21)).calculate_minimum_cost(), Some(11));
+        // a button that doesn't move is never pressed
+        assert_eq!(machine((0, 0), (0, 2), (0, 8)).calculate_minimum_cost(), Some(4));
+        assert_eq!(machine((0, 0), (0, 0), (0, 0)).calculate_minimum_cost(), Some(0));
+        assert_eq!(machine((0, 0), (0, 0), (1, 0)).calculate_minimum_cost(), None);
+
+        // without limits, as far as the prize goes
+        let far = machine((1, 1), (2, 2), (0, 0));
+        assert_eq!(far.calculate_minimum_cost_with_offset(10_000_000_000_000), Some(5_000_000_000_000));
+        assert_eq!(far.calculate_minimum_cost_with_offset(10_000_000_000_001), Some(5_000_000_000_003));
+
+        // the general solver agrees on every collinear case
+        for a in 1..6 {
+            for b in 1..6 {
+                for prize in 0..40 {
+                    let claw = machine((a, 2 * a), (b, 2 * b), (prize, 2 * prize));
+                    let general = claw.to_machine(Some(100)).solve().ok().map(|solution| solution.cost);
+                    assert_eq!(claw.calculate_minimum_cost(), general, "{} {} {}", a, b, prize);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_tolerant_parser() {
+        let input = "button a:X+94,Y+34\r\n  BUTTON B :  x -22 ,  y= +67 , cost 2\nButton C: X=5, Y:-5, limit 7\nPrize: X = 8400, Y=-5400\n\n\
+                     Button A: X+1, Y+1\nButton B: X+1, Y+1\nPrize: X=10, Y=10\n";
+        let machines = parse_machines(input);
+        assert_eq!(machines.len(), 2);
+        let machine = machines[0].as_ref().unwrap();
+        assert_eq!(machine.buttons, vec![
+            Button { name: "a".to_string(), offset: (94, 34), cost: 3, press_limit: None },
+            Button { name: "B".to_string(), offset: (-22, 67), cost: 2, press_limit: None },
+            Button { name: "C".to_string(), offset: (5, -5), cost: 1, press_limit: Some(7) },
+        ]);
+        assert_eq!(machine.prize, (8400, -5400));
+        assert!(machines[1].is_ok());
+
+        let broken = parse_machines("Button A: X+1, Y+1\nButton A: X+2, Y+2\nPrize: X=1, Y=1\n\
+                                     Button A: X+1\nPrize: X=1, Y=1\n\
+                                     Button A: X+1, Y+1, Z+1\nPrize: X=1, Y=1\n\
+                                     Buton B: X+1, Y+1\nPrize: X=1, Y=1\n\
+                                     Button A: X+1, Y+1, cost -1\nPrize: X=1, Y=1\n\
+                                     Button A: X+1, Y+1\nPrize: X=1\n\
+                                     Button A: X+1, Y+1");
+        let reasons: Vec<String> = broken.into_iter().map(|machine| machine.unwrap_err()).collect();
+        assert_eq!(reasons, vec![
+            "Line 2: Button A is given twice",
+            "Line 4: Missing Y",
+            "Line 6: Unknown field 'Z'",
+            "Line 8: Expected a button or a prize, not 'Buton B: X+1, Y+1'",
+            "Line 10: Costs can't be negative",
+            "Line 13: Missing Y",
+            "Machine has no prize",
+        ]);
+
+        // the old fixed format still reads the same
+        assert_eq!(ClawMachine::from_file("test_input.txt").len(), 4);
+    }
+
+    #[test]
+    fn test_reports() {
+        let input = std::fs::read_to_string("test_input.txt").unwrap() + "\nButton A: X+1, Y+1\nPrize: X=3\n";
+        let machines = parse_machines(&input);
+        let reports = reports(&machines, 0, Some(100));
+        assert_eq!(reports.len(), 5);
+        assert_eq!(reports[0], MachineReport {
+            machine: 1,
+            presses: vec![("A".to_string(), 80), ("B".to_string(), 40)],
+            cost: Some(280),
+            reason: None,
+        });
+        assert_eq!(reports[1].reason.as_deref(), Some("No combination of presses reaches the prize"));
+        assert_eq!(reports.iter().filter_map(|report| report.cost).sum::<u128>(), 480);
+
+        let json = reports_to_json(&reports);
+        assert!(json.starts_with("[\n{\"machine\":1,\"solvable\":true,\"presses\":{\"A\":80,\"B\":40},\"cost\":280},\n"));
+        assert!(json.contains("{\"machine\":5,\"solvable\":false,\"reason\":\"Line 18: Missing Y\"}"));
+
+        let table = reports_to_table(&reports);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), 6);
+        assert_eq!(lines[1].split_whitespace().collect::<Vec<_>>(), vec!["1", "yes", "A=80", "B=40", "280"]);
+        assert!(lines[2].starts_with("2        no "));
+
+        let offset_reports = super::reports(&machines, 10_000_000_000_000, None);
+        assert_eq!(offset_reports.iter().filter(|report| report.cost.is_some()).count(), 2);
+    }
 }
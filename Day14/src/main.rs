@@ -6,6 +6,8 @@ use std::ops::Neg;
 use num::integer::lcm;
 use image::{ImageBuffer, Pixel, Rgba, RgbaImage};
 use image::png::PngEncoder;
+use image::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame};
 use regex::Regex;
 
 fn main() {
@@ -15,10 +17,92 @@ fn main() {
     let num_moves = 100;
     let safety_score = move_and_get_safety_score(&robots, bathroom_width, bathroom_height, num_moves);
     println!("Safety score: {}", safety_score);
-    let christmas_tree_time = find_christmas_tree(&robots, bathroom_width, bathroom_height);
+    // --scorer=entropy|variance|component|run picks the measure, --top=n how many times to
+    // list and --crt the per-axis search for scorers that support it
+    let args: Vec<String> = std::env::args().collect();
+    let scorer_name = args.iter().find_map(|arg| arg.strip_prefix("--scorer=")).unwrap_or("entropy");
+    let scorer = built_in_scorers().into_iter().find(|scorer| scorer.name() == scorer_name).expect("Unknown scorer");
+    let top = flag_value(&args, "--top").map_or(1, std::num::NonZeroUsize::get);
+    let crt = args.iter().any(|arg| arg == "--crt");
+    let by_axis = if crt { find_christmas_tree_crt(&robots, bathroom_width, bathroom_height, scorer.as_ref(), top) } else { None };
+    if crt && by_axis.is_none() {
+        eprintln!("The {} scorer doesn't split by axis, so --crt falls back to scanning every time", scorer.name());
+    }
+    let candidates = by_axis.unwrap_or_else(|| find_christmas_tree(&robots, bathroom_width, bathroom_height, scorer.as_ref(), top));
+    for candidate in &candidates {
+        println!("t = {}: {} {:.4}", candidate.time, scorer.name(), candidate.score);
+    }
+    // robots that never move have nothing to search, like before candidates were ranked
+    let christmas_tree_time = candidates.first().map_or(0, |candidate| candidate.time);
     println!("Christmas tree time: {}", christmas_tree_time);   
     let moved_robots = robots.iter().map(|robot| robot.move_robot((christmas_tree_time) as i32, bathroom_width, bathroom_height)).collect::<Vec<Robot>>();
     generate_png(&moved_robots, bathroom_width as u32, bathroom_height as u32, ".", christmas_tree_time as i32).unwrap();
+
+    // --gif=file or --frames=dir renders --from..=--to every --step seconds, with --scale,
+    // --density shading and a --timestamp in the corner
+    let target = args.iter().find_map(|arg| arg.strip_prefix("--gif=").map(|path| (FrameFormat::Gif, path)))
+        .or_else(|| args.iter().find_map(|arg| arg.strip_prefix("--frames=").map(|dir| (FrameFormat::PngSequence, dir))));
+    if let Some((format, output)) = target {
+        let defaults = ExportOptions::default();
+        let options = ExportOptions {
+            from: flag_value(&args, "--from").unwrap_or(defaults.from),
+            to: flag_value(&args, "--to").unwrap_or(defaults.to),
+            step: flag_value(&args, "--step").unwrap_or(defaults.step),
+            scale: flag_value(&args, "--scale").unwrap_or(defaults.scale),
+            density: args.iter().any(|arg| arg == "--density"),
+            timestamp: args.iter().any(|arg| arg == "--timestamp"),
+            format,
+            ..defaults
+        };
+        let frames = export_frames(&robots, bathroom_width, bathroom_height, output, &options).unwrap();
+        println!("Wrote {} frames to {}", frames, output);
+    }
+
+    // --target=file.png or ASCII art finds when the robots best form it, placed --at=x,y and
+    // scored by --metric=iou|hamming
+    if let Some(path) = args.iter().find_map(|arg| arg.strip_prefix("--target=")) {
+        let target = Target::from_file(path).unwrap();
+        let offset = args.iter().find_map(|arg| arg.strip_prefix("--at=")).map(|at| {
+            let (x, y) = at.split_once(',').expect("--at takes x,y");
+            (x.parse().unwrap(), y.parse().unwrap())
+        });
+        let metric = flag_value(&args, "--metric").unwrap_or(MatchMetric::Iou);
+        let best = find_target(&robots, bathroom_width, bathroom_height, &target, offset.unwrap_or((0, 0)), metric).unwrap();
+        println!(
+            "Best match at t = {}: IoU {:.4}, Hamming distance {}, {} of {} target tiles covered",
+            best.time, best.iou, best.hamming, best.covered, best.needed
+        );
+        println!("Covering the target takes {} robots, we have {}", best.needed, robots.len());
+    }
+
+    // --zones=CxR splits the bathroom into a grid, or each --zone=x,y,width,height adds a
+    // rectangle; counts are taken over --from..=--to, listed per second with --zone-counts
+    let zones: Vec<Zone> = args.iter().filter_map(|arg| arg.strip_prefix("--zone=")).map(|zone| zone.parse().unwrap()).collect();
+    let grid = args.iter().find_map(|arg| arg.strip_prefix("--zones=")).map(|grid| {
+        let (columns, rows) = grid.split_once('x').expect("--zones takes CxR");
+        Partition::grid(bathroom_width, bathroom_height, columns.parse().unwrap(), rows.parse().unwrap()).unwrap()
+    });
+    let rectangles = (!zones.is_empty()).then(|| Partition::rectangles(zones, bathroom_width, bathroom_height).unwrap());
+    if let Some(partition) = grid.or(rectangles) {
+        let from = flag_value(&args, "--from").unwrap_or(0);
+        let to = flag_value(&args, "--to").unwrap_or(num_moves as i64);
+        let history = ZoneHistory::record(&robots, bathroom_width, bathroom_height, &partition, from..=to);
+        if args.iter().any(|arg| arg == "--zone-counts") {
+            for ((t, counts), product) in history.times.iter().zip(&history.counts).zip(history.safety_products()) {
+                println!("t = {}: {:?}, safety {}", t, counts, product);
+            }
+        }
+        for (zone, (&(t, count), bounds)) in history.peaks().iter().zip(&partition.zones).enumerate() {
+            println!("Zone {} {:?}: peaks at t = {} with {} robots", zone + 1, bounds, t, count);
+        }
+    }
+}
+
+// The value of a `--name=value` argument
+fn flag_value<T: std::str::FromStr>(args: &[String], name: &str) -> Option<T> {
+    args.iter()
+        .find_map(|arg| arg.strip_prefix(name)?.strip_prefix('='))
+        .map(|value| value.parse().ok().unwrap_or_else(|| panic!("Bad value for {}", name)))
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -58,29 +142,10 @@ impl Robot {
         }
     }
 
+    // 1 to 4 reading left to right, top to bottom, None on the middle lines
+    #[cfg(test)]
     fn determine_quadrant(&self, width: i32, height: i32) -> Option<u8> {
-        let mid_x = width / 2;
-        let mid_y = height / 2;
-        let x = self.pos.0;
-        let y = self.pos.1;
-
-        // Exclude robots on the middle lines
-        if x == mid_x || y == mid_y {
-            return None;
-        }
-
-        // Determine the quadrant
-        if x < mid_x && y < mid_y {
-            Some(1) // Top-left
-        } else if x >= mid_x && y < mid_y {
-            Some(2) // Top-right
-        } else if x < mid_x && y >= mid_y {
-            Some(3) // Bottom-left
-        } else if x >= mid_x && y >= mid_y {
-            Some(4) // Bottom-right
-        } else {
-            panic!("Invalid quadrant");
-        }
+        Partition::grid(width, height, 2, 2).ok()?.zone_of(self.pos).map(|zone| zone as u8 + 1)
     }
 }
 
@@ -111,25 +176,259 @@ fn generate_png(robots: &[Robot], width: u32, height: u32, output_dir: &str, t:
     Ok(())
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FrameFormat {
+    // one animated file
+    Gif,
+    // frame_XXXX.png files in a directory, numbered by time
+    PngSequence,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct ExportOptions {
+    // times from..=to, every step seconds
+    from: i32,
+    to: i32,
+    step: i32,
+    // pixels per tile
+    scale: u32,
+    // shade tiles by how many robots share them rather than plain white
+    density: bool,
+    timestamp: bool,
+    format: FrameFormat,
+    // how long each GIF frame shows for
+    frame_delay_ms: u32,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        ExportOptions { from: 0, to: 100, step: 1, scale: 4, density: false, timestamp: false, format: FrameFormat::Gif, frame_delay_ms: 100 }
+    }
+}
+
+// 3x5 digits for the timestamp, one row of three bits per line
+const DIGITS: [[u8; 5]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b010, 0b010, 0b010],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+
+// The robots after `t` seconds, each tile `scale` pixels square
+fn render_frame(robots: &[Robot], width: i32, height: i32, t: i32, options: &ExportOptions) -> RgbaImage {
+    let scale = options.scale.max(1);
+    let mut counts: HashMap<(i32, i32), u32> = HashMap::new();
+    for robot in robots {
+        *counts.entry(robot.move_robot(t, width, height).pos).or_default() += 1;
+    }
+    let most = counts.values().copied().max().unwrap_or(1);
+    let mut img: RgbaImage = ImageBuffer::from_pixel(width as u32 * scale, height as u32 * scale, Rgba([0, 0, 0, 255]));
+    for (&(x, y), &count) in &counts {
+        // a lone robot is dim and the busiest tile full white
+        let shade = if options.density && most > 1 { (80 + 175 * (count - 1) / (most - 1)) as u8 } else { 255 };
+        for dy in 0..scale {
+            for dx in 0..scale {
+                img.put_pixel(x as u32 * scale + dx, y as u32 * scale + dy, Rgba([shade, shade, shade, 255]));
+            }
+        }
+    }
+    if options.timestamp {
+        draw_number(&mut img, t, scale.div_ceil(2));
+    }
+    img
+}
+
+// Yellow digits on a dark box in the top left corner, `size` pixels per font dot
+fn draw_number(img: &mut RgbaImage, number: i32, size: u32) {
+    let text = number.to_string();
+    let box_width = (text.len() as u32 * 4 + 1) * size;
+    let box_height = 7 * size;
+    for y in 0..box_height.min(img.height()) {
+        for x in 0..box_width.min(img.width()) {
+            img.put_pixel(x, y, Rgba([32, 32, 32, 255]));
+        }
+    }
+    for (index, digit) in text.chars().enumerate() {
+        let Some(rows) = digit.to_digit(10).map(|digit| DIGITS[digit as usize]) else {
+            continue;
+        };
+        for (row, bits) in rows.iter().enumerate() {
+            for column in 0..3 {
+                if bits & (0b100 >> column) == 0 {
+                    continue;
+                }
+                let (left, top) = ((1 + index as u32 * 4 + column) * size, (1 + row as u32) * size);
+                for y in top..(top + size).min(img.height()) {
+                    for x in left..(left + size).min(img.width()) {
+                        img.put_pixel(x, y, Rgba([255, 220, 0, 255]));
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn frame_times(options: &ExportOptions) -> impl Iterator<Item = i32> {
+    (options.from..=options.to).step_by(options.step.max(1) as usize)
+}
+
+// Writes every frame of the range to `output`, a GIF file or a directory for the PNGs,
+// and returns how many there were
+fn export_frames(robots: &[Robot], width: i32, height: i32, output: &str, options: &ExportOptions) -> Result<usize, Box<dyn Error>> {
+    let mut written = 0;
+    match options.format {
+        FrameFormat::Gif => {
+            let mut encoder = GifEncoder::new(File::create(output)?);
+            encoder.set_repeat(Repeat::Infinite)?;
+            for t in frame_times(options) {
+                let delay = Delay::from_numer_denom_ms(options.frame_delay_ms, 1);
+                encoder.encode_frame(Frame::from_parts(render_frame(robots, width, height, t, options), 0, 0, delay))?;
+                written += 1;
+            }
+        }
+        FrameFormat::PngSequence => {
+            std::fs::create_dir_all(output)?;
+            for t in frame_times(options) {
+                let img = render_frame(robots, width, height, t, options);
+                let output_file = File::create(format!("{}/frame_{:04}.png", output, t))?;
+                PngEncoder::new(output_file).encode(&img, img.width(), img.height(), Rgba::<u8>::COLOR_TYPE)?;
+                written += 1;
+            }
+        }
+    }
+    Ok(written)
+}
+
 fn move_and_get_safety_score(robots: &Vec<Robot>, bathroom_width: i32, bathroom_height: i32, num_moves: i32) -> i32 {
-    let mut moved_robots = Vec::new();
-    for robot in robots.iter() {
-        moved_robots.push(robot.move_robot(num_moves, bathroom_width, bathroom_height));
-    }
-
-    let (q1, q2, q3, q4) = moved_robots.iter().fold((0, 0, 0, 0), |acc, robot| {
-        match robot.determine_quadrant(bathroom_width, bathroom_height) {
-            Some(1) => (acc.0 + 1, acc.1, acc.2, acc.3),
-            Some(2) => (acc.0, acc.1 + 1, acc.2, acc.3),
-            Some(3) => (acc.0, acc.1, acc.2 + 1, acc.3),
-            Some(4) => (acc.0, acc.1, acc.2, acc.3 + 1),
-            None => acc,
-            _ => acc,
+    let moved_robots = moved(robots, num_moves as i64, bathroom_width, bathroom_height);
+    // a room too narrow for four quadrants has an empty one, so nothing is safe
+    Partition::grid(bathroom_width, bathroom_height, 2, 2).map_or(0, |partition| safety_product(&partition.counts(&moved_robots)) as i32)
+}
+
+// A rectangle of tiles, `x..x + width` by `y..y + height`
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Zone {
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+}
+
+impl Zone {
+    fn contains(&self, (x, y): (i32, i32)) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+}
+
+// "x,y,width,height"
+impl std::str::FromStr for Zone {
+    type Err = String;
+
+    fn from_str(zone: &str) -> Result<Self, Self::Err> {
+        let values = zone
+            .split(',')
+            .map(|value| value.trim().parse::<i32>().map_err(|e| format!("Bad zone {}: {}", zone, e)))
+            .collect::<Result<Vec<_>, _>>()?;
+        match values[..] {
+            [x, y, width, height] if width > 0 && height > 0 => Ok(Zone { x, y, width, height }),
+            _ => Err(format!("Zone should be x,y,width,height: {}", zone)),
         }
-    });
+    }
+}
+
+// Zones of the bathroom a robot can be counted in. Zones may overlap, in which case a
+// robot counts towards each of them
+#[derive(Debug, Clone, PartialEq)]
+struct Partition {
+    zones: Vec<Zone>,
+}
+
+impl Partition {
+    // `columns` by `rows` zones, read left to right, top to bottom. Like the quadrants, the
+    // lines between zones at i * width / columns and j * height / rows belong to none, so
+    // every zone needs at least one tile besides them
+    fn grid(width: i32, height: i32, columns: i32, rows: i32) -> Result<Partition, String> {
+        if columns <= 0 || rows <= 0 {
+            return Err(format!("A grid of zones needs at least one column and row, not {}x{}", columns, rows));
+        }
+        let spans = |size: i32, parts: i32| -> Vec<(i32, i32)> {
+            (0..parts)
+                .map(|i| {
+                    let start = if i == 0 { 0 } else { i * size / parts + 1 };
+                    let end = if i == parts - 1 { size } else { (i + 1) * size / parts };
+                    (start, end)
+                })
+                .collect()
+        };
+        let xs = spans(width, columns);
+        let ys = spans(height, rows);
+        let zones = ys
+            .iter()
+            .flat_map(|&(y0, y1)| xs.iter().map(move |&(x0, x1)| Zone { x: x0, y: y0, width: x1 - x0, height: y1 - y0 }))
+            .collect();
+        let partition = Partition { zones };
+        if partition.zones.iter().any(|zone| zone.width < 1 || zone.height < 1) {
+            return Err(format!("{}x{} zones don't fit in a {}x{} bathroom", columns, rows, width, height));
+        }
+        Ok(partition)
+    }
+
+    // Zones of the caller's choosing, each of which has to be inside the bathroom
+    fn rectangles(zones: Vec<Zone>, width: i32, height: i32) -> Result<Partition, String> {
+        let room = Zone { x: 0, y: 0, width, height };
+        if let Some(zone) = zones.iter().find(|zone| !room.contains((zone.x, zone.y)) || !room.contains((zone.x + zone.width - 1, zone.y + zone.height - 1))) {
+            return Err(format!("{:?} isn't inside the {}x{} bathroom", zone, width, height));
+        }
+        Ok(Partition { zones })
+    }
+
+    #[cfg(test)]
+    fn zone_of(&self, pos: (i32, i32)) -> Option<usize> {
+        self.zones.iter().position(|zone| zone.contains(pos))
+    }
 
-    let safety_score = q1 * q2 * q3 * q4;
-    safety_score
+    fn counts(&self, robots: &[Robot]) -> Vec<usize> {
+        self.zones.iter().map(|zone| robots.iter().filter(|robot| zone.contains(robot.pos)).count()).collect()
+    }
+}
+
+fn safety_product(counts: &[usize]) -> u64 {
+    counts.iter().map(|&count| count as u64).product()
+}
+
+// Robots in each zone at each of `times`
+#[derive(Debug, Clone, PartialEq)]
+struct ZoneHistory {
+    times: Vec<i64>,
+    counts: Vec<Vec<usize>>,
+}
+
+impl ZoneHistory {
+    fn record(robots: &[Robot], width: i32, height: i32, partition: &Partition, times: impl Iterator<Item = i64>) -> ZoneHistory {
+        let times: Vec<i64> = times.collect();
+        let counts = times.iter().map(|&t| partition.counts(&moved(robots, t, width, height))).collect();
+        ZoneHistory { times, counts }
+    }
+
+    fn safety_products(&self) -> Vec<u64> {
+        self.counts.iter().map(|counts| safety_product(counts)).collect()
+    }
+
+    // The earliest time each zone is at its fullest, and how full that is
+    fn peaks(&self) -> Vec<(i64, usize)> {
+        let zones = self.counts.first().map_or(0, |counts| counts.len());
+        (0..zones)
+            .map(|zone| {
+                self.times.iter().zip(&self.counts).fold((self.times[0], 0), |peak, (&t, counts)| if counts[zone] > peak.1 { (t, counts[zone]) } else { peak })
+            })
+            .collect()
+    }
 }
 
 fn calculate_entropy(robots: &[Robot], bin_size: i32) -> f64 {
@@ -176,20 +475,301 @@ fn calculate_cycle_length(robots: &[Robot], width: i32, height: i32) -> i64 {
     }).fold(1, lcm)
 }
 
-fn find_christmas_tree(robots: &[Robot], bathroom_width: i32, bathroom_height: i32) -> i64 {
-    let cycle_length = calculate_cycle_length(&robots, bathroom_width, bathroom_height);
-    // for each time until cycle length, move the robots, then measure the entropy, get the time with the lowest entropy
-    let mut min_entropy = std::f64::INFINITY;
-    let mut min_entropy_time = 0;
-    for t in 1..cycle_length {
-        let moved_robots = robots.iter().map(|robot| robot.move_robot(t as i32, bathroom_width, bathroom_height)).collect::<Vec<Robot>>();
-        let entropy = calculate_entropy(&moved_robots, 10);
-        if entropy < min_entropy {
-            min_entropy = entropy;
-            min_entropy_time = t;
+// How structured a set of robots looks, higher being more structured
+trait FormationScorer {
+    fn name(&self) -> &'static str;
+    fn score(&self, robots: &[Robot], width: i32, height: i32) -> f64;
+    // The same measure for one axis, if the scorer splits into x and y parts. x repeats
+    // every `width` seconds and y every `height`, which is what the CRT search relies on
+    fn axis_score(&self, _values: &[i32]) -> Option<f64> {
+        None
+    }
+}
+
+// Shannon entropy of robots counted in square bins, negated so clustered is high
+struct BinnedEntropy {
+    bin_size: i32,
+}
+
+// Spread of the robots about their centre, negated so compact is high
+struct Variance;
+
+// The most tiles joined up edge to edge
+struct LargestComponent;
+
+// The most occupied tiles side by side in a row
+struct LongestRun;
+
+fn axis_entropy(values: &[i32], bin_size: i32) -> f64 {
+    let mut counts: HashMap<i32, usize> = HashMap::new();
+    for value in values {
+        *counts.entry(value / bin_size).or_default() += 1;
+    }
+    let total = values.len() as f64;
+    counts.values().map(|&count| count as f64 / total).map(|p| p * p.log2()).sum::<f64>().neg()
+}
+
+fn variance(values: &[i32]) -> f64 {
+    let total = values.len().max(1) as f64;
+    let mean = values.iter().map(|&value| value as f64).sum::<f64>() / total;
+    values.iter().map(|&value| (value as f64 - mean).powi(2)).sum::<f64>() / total
+}
+
+impl FormationScorer for BinnedEntropy {
+    fn name(&self) -> &'static str {
+        "entropy"
+    }
+
+    fn score(&self, robots: &[Robot], _width: i32, _height: i32) -> f64 {
+        -calculate_entropy(robots, self.bin_size)
+    }
+
+    fn axis_score(&self, values: &[i32]) -> Option<f64> {
+        Some(-axis_entropy(values, self.bin_size))
+    }
+}
+
+impl FormationScorer for Variance {
+    fn name(&self) -> &'static str {
+        "variance"
+    }
+
+    fn score(&self, robots: &[Robot], _width: i32, _height: i32) -> f64 {
+        let xs: Vec<i32> = robots.iter().map(|robot| robot.pos.0).collect();
+        let ys: Vec<i32> = robots.iter().map(|robot| robot.pos.1).collect();
+        -(variance(&xs) + variance(&ys))
+    }
+
+    fn axis_score(&self, values: &[i32]) -> Option<f64> {
+        Some(-variance(values))
+    }
+}
+
+impl FormationScorer for LargestComponent {
+    fn name(&self) -> &'static str {
+        "component"
+    }
+
+    fn score(&self, robots: &[Robot], _width: i32, _height: i32) -> f64 {
+        let mut unvisited: std::collections::HashSet<(i32, i32)> = robots.iter().map(|robot| robot.pos).collect();
+        let mut largest = 0;
+        while let Some(&start) = unvisited.iter().next() {
+            unvisited.remove(&start);
+            let mut stack = vec![start];
+            let mut size = 0;
+            while let Some((x, y)) = stack.pop() {
+                size += 1;
+                for next in [(x + 1, y), (x - 1, y), (x, y + 1), (x, y - 1)] {
+                    if unvisited.remove(&next) {
+                        stack.push(next);
+                    }
+                }
+            }
+            largest = largest.max(size);
+        }
+        largest as f64
+    }
+}
+
+impl FormationScorer for LongestRun {
+    fn name(&self) -> &'static str {
+        "run"
+    }
+
+    fn score(&self, robots: &[Robot], _width: i32, _height: i32) -> f64 {
+        let mut tiles: Vec<(i32, i32)> = robots.iter().map(|robot| (robot.pos.1, robot.pos.0)).collect();
+        tiles.sort();
+        tiles.dedup();
+        let (mut longest, mut run) = (0, 0);
+        for (index, &(y, x)) in tiles.iter().enumerate() {
+            run = if index > 0 && tiles[index - 1] == (y, x - 1) { run + 1 } else { 1 };
+            longest = longest.max(run);
+        }
+        longest as f64
+    }
+}
+
+fn built_in_scorers() -> Vec<Box<dyn FormationScorer>> {
+    vec![Box::new(BinnedEntropy { bin_size: 10 }), Box::new(Variance), Box::new(LargestComponent), Box::new(LongestRun)]
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Candidate {
+    time: i64,
+    score: f64,
+}
+
+// Best first, earlier times first among equal scores
+fn top_candidates(mut candidates: Vec<Candidate>, top: usize) -> Vec<Candidate> {
+    candidates.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.time.cmp(&b.time)));
+    candidates.truncate(top);
+    candidates
+}
+
+fn moved(robots: &[Robot], t: i64, width: i32, height: i32) -> Vec<Robot> {
+    robots.iter().map(|robot| robot.move_robot(t as i32, width, height)).collect()
+}
+
+// The times a formation is looked for, every state the robots reach bar the starting one
+fn search_times(robots: &[Robot], width: i32, height: i32) -> std::ops::Range<i64> {
+    1..calculate_cycle_length(robots, width, height)
+}
+
+// Scores every time in the cycle and keeps the `top` most structured
+fn find_christmas_tree(robots: &[Robot], bathroom_width: i32, bathroom_height: i32, scorer: &dyn FormationScorer, top: usize) -> Vec<Candidate> {
+    let candidates = search_times(robots, bathroom_width, bathroom_height)
+        .map(|t| Candidate { time: t, score: scorer.score(&moved(robots, t, bathroom_width, bathroom_height), bathroom_width, bathroom_height) })
+        .collect();
+    top_candidates(candidates, top)
+}
+
+// The best `top` times on each axis on their own, x over one width of seconds and y over
+// one height, paired up by the Chinese remainder theorem and scored in full. None when the
+// scorer doesn't split by axis
+fn find_christmas_tree_crt(robots: &[Robot], width: i32, height: i32, scorer: &dyn FormationScorer, top: usize) -> Option<Vec<Candidate>> {
+    let axis_best = |size: i32, axis: fn(&Robot) -> i32| -> Option<Vec<Candidate>> {
+        let candidates = (0..size as i64)
+            .map(|t| {
+                let values: Vec<i32> = moved(robots, t, width, height).iter().map(axis).collect();
+                scorer.axis_score(&values).map(|score| Candidate { time: t, score })
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Some(top_candidates(candidates, top))
+    };
+    let xs = axis_best(width, |robot| robot.pos.0)?;
+    let ys = axis_best(height, |robot| robot.pos.1)?;
+    // the robots may all repeat sooner than width x height, so fold into the same range
+    // the full scan covers
+    let times = search_times(robots, width, height);
+    let candidates = xs
+        .iter()
+        .flat_map(|x| ys.iter().filter_map(move |y| chinese_remainder((x.time, width as i64), (y.time, height as i64))))
+        .map(|t| t % times.end.max(1))
+        .filter(|t| times.contains(t))
+        .collect::<std::collections::BTreeSet<i64>>()
+        .into_iter()
+        .map(|t| Candidate { time: t, score: scorer.score(&moved(robots, t, width, height), width, height) })
+        .collect();
+    Some(top_candidates(candidates, top))
+}
+
+// The smallest t >= 0 with t ≡ a (mod m) and t ≡ b (mod n), if there is one
+fn chinese_remainder((a, m): (i64, i64), (b, n): (i64, i64)) -> Option<i64> {
+    let g = num::integer::gcd(m, n);
+    if (b - a) % g != 0 {
+        return None;
+    }
+    let modulus = m / g * n;
+    let step = (0..n / g).find(|&k| (a + k * m - b).rem_euclid(n) == 0)?;
+    Some((a + step * m).rem_euclid(modulus))
+}
+
+// A formation to look for: the tiles a robot should stand on, relative to the top left
+#[derive(Debug, Clone, PartialEq)]
+struct Target {
+    width: i32,
+    height: i32,
+    tiles: std::collections::HashSet<(i32, i32)>,
+}
+
+impl Target {
+    // '#' (or any mark other than '.' and space) is a robot
+    fn from_ascii(art: &str) -> Result<Target, String> {
+        let rows: Vec<&str> = art.lines().collect();
+        let tiles: std::collections::HashSet<(i32, i32)> = rows
+            .iter()
+            .enumerate()
+            .flat_map(|(y, row)| row.chars().enumerate().filter(|&(_, c)| c != '.' && c != ' ').map(move |(x, _)| (x as i32, y as i32)))
+            .collect();
+        if tiles.is_empty() {
+            return Err("Target has no robots in it".to_string());
+        }
+        let width = rows.iter().map(|row| row.chars().count()).max().unwrap_or(0) as i32;
+        Ok(Target { width, height: rows.len() as i32, tiles })
+    }
+
+    // Bright pixels are robots, the same as generate_png draws them
+    fn from_png(path: &str) -> Result<Target, Box<dyn Error>> {
+        let img = image::open(path)?.to_luma8();
+        let tiles: std::collections::HashSet<(i32, i32)> =
+            img.enumerate_pixels().filter(|(_, _, pixel)| pixel.0[0] >= 128).map(|(x, y, _)| (x as i32, y as i32)).collect();
+        if tiles.is_empty() {
+            return Err("Target has no robots in it".into());
+        }
+        Ok(Target { width: img.width() as i32, height: img.height() as i32, tiles })
+    }
+
+    fn from_file(path: &str) -> Result<Target, Box<dyn Error>> {
+        if path.to_lowercase().ends_with(".png") {
+            Target::from_png(path)
+        } else {
+            Ok(Target::from_ascii(&std::fs::read_to_string(path)?)?)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum MatchMetric {
+    // Shared tiles over tiles in either, higher is better
+    Iou,
+    // Tiles in one but not the other, lower is better
+    Hamming,
+}
+
+impl std::str::FromStr for MatchMetric {
+    type Err = String;
+
+    fn from_str(metric: &str) -> Result<Self, Self::Err> {
+        match metric {
+            "iou" => Ok(MatchMetric::Iou),
+            "hamming" => Ok(MatchMetric::Hamming),
+            _ => Err(format!("Unknown metric: {}", metric)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct TargetMatch {
+    time: i64,
+    iou: f64,
+    hamming: usize,
+    // target tiles with a robot on them
+    covered: usize,
+    // robots it takes to cover the target, one per tile
+    needed: usize,
+}
+
+impl TargetMatch {
+    fn better_than(&self, other: &TargetMatch, metric: MatchMetric) -> bool {
+        match metric {
+            MatchMetric::Iou => self.iou > other.iou,
+            MatchMetric::Hamming => self.hamming < other.hamming,
         }
     }
-    min_entropy_time
+}
+
+// How well the robots at time `t` line up with the target placed at `offset`. Only the
+// target's own rectangle is compared, robots elsewhere in the bathroom don't count
+fn match_target(robots: &[Robot], width: i32, height: i32, target: &Target, offset: (i32, i32), t: i64) -> TargetMatch {
+    let window = Zone { x: offset.0, y: offset.1, width: target.width, height: target.height };
+    let occupied: std::collections::HashSet<(i32, i32)> =
+        moved(robots, t, width, height).iter().map(|robot| robot.pos).filter(|&pos| window.contains(pos)).collect();
+    let placed: std::collections::HashSet<(i32, i32)> = target.tiles.iter().map(|&(x, y)| (x + offset.0, y + offset.1)).collect();
+    let covered = placed.intersection(&occupied).count();
+    let union = placed.len() + occupied.len() - covered;
+    TargetMatch { time: t, iou: covered as f64 / union as f64, hamming: union - covered, covered, needed: placed.len() }
+}
+
+// The time in the cycle at which the robots best match the target, earliest on a tie
+fn find_target(robots: &[Robot], width: i32, height: i32, target: &Target, offset: (i32, i32), metric: MatchMetric) -> Result<TargetMatch, String> {
+    if offset.0 < 0 || offset.1 < 0 || offset.0 + target.width > width || offset.1 + target.height > height {
+        return Err(format!("A {}x{} target at {:?} doesn't fit in the {}x{} bathroom", target.width, target.height, offset, width, height));
+    }
+    let best = (0..calculate_cycle_length(robots, width, height))
+        .map(|t| match_target(robots, width, height, target, offset, t))
+        .reduce(|best, candidate| if candidate.better_than(&best, metric) { candidate } else { best })
+        .ok_or("No robots to move")?;
+    Ok(best)
 }
 
 #[cfg(test)]
@@ -262,9 +842,180 @@ mod tests {
         let bathroom_width = 101;
         let bathroom_height = 103;
 
-        let min_entropy_time = find_christmas_tree(&mut robots, bathroom_width, bathroom_height);
+        let min_entropy_time = find_christmas_tree(&mut robots, bathroom_width, bathroom_height, &BinnedEntropy { bin_size: 10 }, 1)[0].time;
         assert_eq!(min_entropy_time, 6577);
     }
 
-    
-}
\ No newline at end of file
+    #[test]
+    fn test_render_frame() {
+        let robots = vec![Robot { pos: (1, 1), vel: (1, 0) }, Robot { pos: (2, 1), vel: (0, 0) }, Robot { pos: (0, 0), vel: (0, 0) }];
+        let options = ExportOptions { scale: 2, ..ExportOptions::default() };
+        let img = render_frame(&robots, 4, 3, 1, &options);
+        assert_eq!(img.dimensions(), (8, 6));
+        assert_eq!(img.get_pixel(5, 3), &Rgba([255, 255, 255, 255]));
+        assert_eq!(img.get_pixel(2, 2), &Rgba([0, 0, 0, 255]));
+
+        // two robots share (2, 1) at t = 1, so the lone one at (0, 0) is the dimmest shade
+        let shaded = render_frame(&robots, 4, 3, 1, &ExportOptions { density: true, ..options.clone() });
+        assert_eq!(shaded.get_pixel(4, 2), &Rgba([255, 255, 255, 255]));
+        assert_eq!(shaded.get_pixel(0, 0), &Rgba([80, 80, 80, 255]));
+
+        let stamped = render_frame(&robots, 20, 10, 7, &ExportOptions { timestamp: true, scale: 1, ..options });
+        // the top row of a 7 is solid, its second row only on the right
+        assert_eq!(stamped.get_pixel(1, 1), &Rgba([255, 220, 0, 255]));
+        assert_eq!(stamped.get_pixel(1, 2), &Rgba([32, 32, 32, 255]));
+        assert_eq!(stamped.get_pixel(3, 2), &Rgba([255, 220, 0, 255]));
+    }
+
+    #[test]
+    fn test_export_frames() {
+        let robots = Robot::from_file("test_input.txt");
+        let dir = std::env::temp_dir().join(format!("day14_frames_{}", std::process::id()));
+        let options = ExportOptions { from: 3, to: 9, step: 3, format: FrameFormat::PngSequence, ..ExportOptions::default() };
+        assert_eq!(frame_times(&options).collect::<Vec<_>>(), vec![3, 6, 9]);
+        assert_eq!(export_frames(&robots, 11, 7, dir.to_str().unwrap(), &options).unwrap(), 3);
+        let frame = image::open(dir.join("frame_0006.png")).unwrap().to_rgba8();
+        assert_eq!(frame, render_frame(&robots, 11, 7, 6, &options));
+
+        let gif = dir.join("robots.gif");
+        let options = ExportOptions { to: 4, timestamp: true, ..ExportOptions::default() };
+        assert_eq!(export_frames(&robots, 11, 7, gif.to_str().unwrap(), &options).unwrap(), 5);
+        let bytes = std::fs::read(&gif).unwrap();
+        assert!(bytes.starts_with(b"GIF89a"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    // a 6x5 block formed at `time`, among robots wandering at random
+    fn swarm_forming_block(time: i32) -> Vec<Robot> {
+        let mut seed = 99u64;
+        let mut next = |bound: i32| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            ((seed >> 33) % bound as u64) as i32
+        };
+        let mut robots = Vec::new();
+        for i in 0..60 {
+            let vel = (next(201) - 100, next(207) - 103);
+            let pos = if i < 30 { (40 + i % 6, 50 + i / 6) } else { (next(101), next(103)) };
+            // run the robot backwards from where it should be at `time`
+            let start = Robot { pos, vel: (-vel.0, -vel.1) }.move_robot(time, 101, 103);
+            robots.push(Robot { pos: start.pos, vel });
+        }
+        robots
+    }
+
+    #[test]
+    fn test_formation_scorers() {
+        let robots = |tiles: &[(i32, i32)]| tiles.iter().map(|&pos| Robot { pos, vel: (0, 0) }).collect::<Vec<_>>();
+        let shape = robots(&[(0, 0), (1, 0), (2, 0), (2, 1), (5, 5), (6, 5), (5, 5), (9, 0)]);
+        assert_eq!(LargestComponent.score(&shape, 11, 7), 4.0);
+        assert_eq!(LongestRun.score(&shape, 11, 7), 3.0);
+        assert_eq!(Variance.score(&robots(&[(0, 0), (2, 2)]), 11, 7), -2.0);
+        assert_eq!(BinnedEntropy { bin_size: 10 }.score(&shape, 11, 7), 0.0);
+        assert_eq!(built_in_scorers().iter().map(|scorer| scorer.name()).collect::<Vec<_>>(), vec!["entropy", "variance", "component", "run"]);
+        assert_eq!(LongestRun.axis_score(&[1, 2]), None);
+    }
+
+    #[test]
+    fn test_find_formation() {
+        let swarm = swarm_forming_block(4321);
+        for scorer in built_in_scorers() {
+            let candidates = find_christmas_tree(&swarm, 101, 103, scorer.as_ref(), 3);
+            assert_eq!(candidates.len(), 3);
+            assert_eq!(candidates[0].time, 4321, "{}", scorer.name());
+            assert!(candidates[0].score > candidates[1].score);
+        }
+        let crt = find_christmas_tree_crt(&swarm, 101, 103, &Variance, 3).unwrap();
+        assert_eq!(crt[0], find_christmas_tree(&swarm, 101, 103, &Variance, 1)[0]);
+        assert_eq!(crt.len(), 3);
+        assert_eq!(find_christmas_tree_crt(&swarm, 101, 103, &BinnedEntropy { bin_size: 10 }, 2).unwrap()[0].time, 4321);
+        assert!(find_christmas_tree_crt(&swarm, 101, 103, &LargestComponent, 1).is_none());
+
+        // robots that never move have no times to search, by either route
+        let still: Vec<Robot> = swarm.iter().map(|robot| Robot { pos: robot.pos, vel: (0, 0) }).collect();
+        assert!(find_christmas_tree(&still, 101, 103, &Variance, 3).is_empty());
+        assert_eq!(find_christmas_tree_crt(&still, 101, 103, &Variance, 3), Some(Vec::new()));
+        // a formation at the start is outside the range for both
+        let start = swarm_forming_block(0);
+        assert!(find_christmas_tree_crt(&start, 101, 103, &Variance, 5).unwrap().iter().all(|candidate| candidate.time > 0));
+    }
+
+    #[test]
+    fn test_chinese_remainder() {
+        assert_eq!(chinese_remainder((80, 101), (82, 103)), Some(10382));
+        assert_eq!(chinese_remainder((2, 4), (3, 6)), None);
+        assert_eq!(chinese_remainder((2, 4), (4, 6)), Some(10));
+    }
+
+    #[test]
+    fn test_target_from_ascii() {
+        let target = Target::from_ascii("..#\n.##\n").unwrap();
+        assert_eq!((target.width, target.height), (3, 2));
+        assert_eq!(target.tiles, [(2, 0), (1, 1), (2, 1)].into_iter().collect());
+        assert!(Target::from_ascii("...\n").is_err());
+        assert_eq!("hamming".parse::<MatchMetric>(), Ok(MatchMetric::Hamming));
+    }
+
+    #[test]
+    fn test_find_target() {
+        let swarm = swarm_forming_block(4321);
+        let block = Target::from_ascii(&"######\n".repeat(5)).unwrap();
+        let best = find_target(&swarm, 101, 103, &block, (40, 50), MatchMetric::Hamming).unwrap();
+        assert_eq!(best.time, 4321);
+        assert_eq!((best.covered, best.needed, best.hamming), (30, 30, 0));
+        assert_eq!(best.iou, 1.0);
+        assert_eq!(find_target(&swarm, 101, 103, &block, (40, 50), MatchMetric::Iou).unwrap().time, 4321);
+        assert!(find_target(&swarm, 101, 103, &block, (98, 0), MatchMetric::Iou).is_err());
+    }
+
+    #[test]
+    fn test_target_from_png() {
+        let robots: Vec<Robot> = [(1, 0), (0, 2)].iter().map(|&pos| Robot { pos, vel: (0, 0) }).collect();
+        let dir = std::env::temp_dir().join(format!("day14_target_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        generate_png(&robots, 3, 4, dir.to_str().unwrap(), 0).unwrap();
+        let target = Target::from_file(dir.join("frame_0000.png").to_str().unwrap()).unwrap();
+        assert_eq!((target.width, target.height), (3, 4));
+        assert_eq!(target.tiles, [(1, 0), (0, 2)].into_iter().collect());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_partition_grid() {
+        let quadrants = Partition::grid(11, 7, 2, 2).unwrap();
+        assert_eq!(quadrants.zones[0], Zone { x: 0, y: 0, width: 5, height: 3 });
+        assert_eq!(quadrants.zones[3], Zone { x: 6, y: 4, width: 5, height: 3 });
+        assert_eq!(quadrants.zone_of((5, 1)), None);
+        assert_eq!(quadrants.zone_of((7, 1)), Some(1));
+        let thirds = Partition::grid(101, 103, 3, 1).unwrap();
+        assert_eq!(thirds.zones.iter().map(|zone| (zone.x, zone.width, zone.height)).collect::<Vec<_>>(), vec![(0, 33, 103), (34, 33, 103), (68, 33, 103)]);
+        assert_eq!("1,2,3,4".parse::<Zone>(), Ok(Zone { x: 1, y: 2, width: 3, height: 4 }));
+        assert!("1,2,0,4".parse::<Zone>().is_err());
+        assert!("1,2,3".parse::<Zone>().is_err());
+
+        assert!(Partition::grid(11, 7, 0, 2).is_err());
+        assert!(Partition::grid(11, 7, -1, 2).is_err());
+        assert!(Partition::grid(11, 7, 20, 1).is_err());
+        assert!(Partition::grid(11, 7, 6, 1).is_ok());
+        assert!(Partition::grid(11, 7, 7, 1).is_err());
+        assert_eq!(Partition::rectangles(vec!["0,0,11,7".parse().unwrap()], 11, 7).unwrap().zones.len(), 1);
+        assert!(Partition::rectangles(vec!["5,5,7,1".parse().unwrap()], 11, 7).is_err());
+        assert!(Partition::rectangles(vec!["-1,0,2,2".parse().unwrap()], 11, 7).is_err());
+        // too narrow to split, as with the old quadrants nothing counts as safe
+        assert_eq!(move_and_get_safety_score(&vec![Robot { pos: (0, 0), vel: (0, 0) }], 2, 7, 0), 0);
+    }
+
+    #[test]
+    fn test_zone_history() {
+        let robots = Robot::from_file("test_input.txt");
+        let quadrants = Partition::grid(11, 7, 2, 2).unwrap();
+        let history = ZoneHistory::record(&robots, 11, 7, &quadrants, 99..=100);
+        assert_eq!(history.counts[1], vec![1, 3, 4, 1]);
+        assert_eq!(history.safety_products()[1], 12);
+
+        let stack = [(0, 0), (0, 1), (3, 3)].map(|pos| Robot { pos, vel: (1, 0) });
+        let partition = Partition::rectangles(vec!["0,0,2,2".parse().unwrap(), "1,0,3,4".parse().unwrap()], 11, 7).unwrap();
+        let history = ZoneHistory::record(&stack, 11, 7, &partition, 0..=3);
+        assert_eq!(history.counts, vec![vec![2, 1], vec![2, 2], vec![0, 2], vec![0, 2]]);
+        assert_eq!(history.peaks(), vec![(0, 2), (1, 2)]);
+    }
+}
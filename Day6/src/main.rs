@@ -8,14 +8,43 @@ use rayon::prelude::*;
 
 fn main() {
     let input_file = "input.txt";
-    let mut grid = load_grid(input_file).expect("Failed to load grid");
+    let grid = load_grid(input_file).expect("Failed to load grid");
     let position = find_guard_position(&grid).expect("No Guard Found");
     let mut guard = Guard::new(position, Direction::UP);
     guard.move_until_left_or_looped(&grid);
     println!("Visited: {}", guard.visited.len());
-    let loops = find_looping_positions(&mut grid, position);
-    println!("Loops: {}", loops);
-
+    let args: Vec<String> = std::env::args().collect();
+    let obstructions = if args.iter().any(|arg| arg == "--parallel") {
+        find_looping_positions_parallel(grid.clone(), position)
+    } else {
+        find_looping_positions(&grid, position)
+    };
+    println!("Loops: {}", obstructions.len());
+    if args.iter().any(|arg| arg == "--list") {
+        for obstruction in &obstructions {
+            println!("{:?}: loop of {} steps, entered at step {}",
+                     obstruction.position, obstruction.loop_length, obstruction.entry_step);
+        }
+    }
+    // --render=row,col draws the patrol with an obstruction at that position
+    if let Some(target) = args.iter().find_map(|arg| arg.strip_prefix("--render=")) {
+        let (row, col) = target.split_once(',').expect("Expected --render=row,col");
+        let obstruction = (row.parse().expect("Bad row"), col.parse().expect("Bad col"));
+        print!("{}", render_patrol(&grid, position, obstruction));
+    }
+    // --patrol runs every guard on the map, optionally with --turn=left|right|reverse and --guards-block
+    if args.iter().any(|arg| arg == "--patrol") {
+        let turn_rule = args.iter()
+            .find_map(|arg| arg.strip_prefix("--turn="))
+            .map(|rule| rule.parse().expect("Unknown turn rule"))
+            .unwrap_or(TurnRule::Right);
+        let guards_block = args.iter().any(|arg| arg == "--guards-block");
+        let report = Patrol::from_grid(&grid, turn_rule, guards_block).run(&grid);
+        for guard in &report.guards {
+            println!("Guard from {:?}: covered {}, {:?}", guard.start, guard.coverage, guard.status);
+        }
+        println!("Patrol: {:?}", report.status);
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
@@ -31,6 +60,9 @@ impl Direction {
     const LEFT: Direction = Direction { d_row: 0, d_col: -1 };
     const RIGHT: Direction = Direction { d_row: 0, d_col: 1 };
 
+    // Clockwise order, so turning right is a step to the next index
+    const ALL: [Direction; 4] = [Direction::UP, Direction::RIGHT, Direction::DOWN, Direction::LEFT];
+
     // Method to turn right
     fn turn_right(&self) -> Direction {
         match *self {
@@ -41,16 +73,66 @@ impl Direction {
             _ => *self, // Default case if no match (shouldn't happen for valid directions)
         }
     }
+
+    fn turn_left(&self) -> Direction {
+        self.turn_right().turn_right().turn_right()
+    }
+
+    fn reverse(&self) -> Direction {
+        self.turn_right().turn_right()
+    }
+
+    fn from_marker(marker: char) -> Option<Direction> {
+        match marker {
+            '^' => Some(Direction::UP),
+            '>' => Some(Direction::RIGHT),
+            'v' => Some(Direction::DOWN),
+            '<' => Some(Direction::LEFT),
+            _ => None,
+        }
+    }
+}
+
+// What a guard does when something is in front of them
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum TurnRule {
+    Right,
+    Left,
+    Reverse,
+}
+
+impl TurnRule {
+    fn turn(&self, direction: Direction) -> Direction {
+        match self {
+            TurnRule::Right => direction.turn_right(),
+            TurnRule::Left => direction.turn_left(),
+            TurnRule::Reverse => direction.reverse(),
+        }
+    }
+}
+
+impl std::str::FromStr for TurnRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "right" => Ok(TurnRule::Right),
+            "left" => Ok(TurnRule::Left),
+            "reverse" => Ok(TurnRule::Reverse),
+            _ => Err(format!("Unknown turn rule: {}", s)),
+        }
+    }
 }
 
 struct Guard {
     position: (usize, usize),
     direction: Direction,
+    turn_rule: TurnRule,
     visited_with_direction: HashSet<((usize, usize), Direction)>,
     visited: HashSet<(usize, usize)>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 enum LoopOrExit {
     Loop,
     Exit,
@@ -61,6 +143,10 @@ enum LoopOrExit {
 
 impl Guard {
     fn new(position: (usize, usize), direction: Direction) -> Guard {
+        Guard::with_rule(position, direction, TurnRule::Right)
+    }
+
+    fn with_rule(position: (usize, usize), direction: Direction, turn_rule: TurnRule) -> Guard {
         let mut visited = HashSet::new();
         visited.insert(position);
         let mut visited_with_direction = HashSet::new();
@@ -68,27 +154,52 @@ impl Guard {
         Guard {
             position,
             direction,
+            turn_rule,
             visited_with_direction,
             visited,
         }
     }
 
-    fn move_guard(&mut self, grid: &Grid<char>) -> LoopOrExit {
+    fn next_position(&self, grid: &Grid<char>) -> Option<(usize, usize)> {
         let (row, col) = self.position;
-        let new_row = (row as isize + self.direction.d_row) as usize;
-        let new_col = (col as isize + self.direction.d_col) as usize;
-        if grid.get(new_row, new_col).is_none() {
+        let new_row = row.checked_add_signed(self.direction.d_row)?;
+        let new_col = col.checked_add_signed(self.direction.d_col)?;
+        grid.get(new_row, new_col).map(|_| (new_row, new_col))
+    }
+
+    fn move_guard(&mut self, grid: &Grid<char>) -> LoopOrExit {
+        let Some(next) = self.next_position(grid) else {
             return LoopOrExit::Exit
+        };
+        if grid[next] == '#' {
+            self.direction = self.turn_rule.turn(self.direction);
+            // a guard boxed in only ever turns, so turning counts as being somewhere too
+            if !self.visited_with_direction.insert((self.position, self.direction)) {
+                return LoopOrExit::Loop
+            }
         }
-        if grid.get(new_row, new_col) == Some(&'#') {
-            //turn right
-            self.direction = self.direction.turn_right();
-        }
-        else if self.visited_with_direction.contains(&((new_row, new_col), self.direction)) {
+        else if self.visited_with_direction.contains(&(next, self.direction)) {
             return LoopOrExit::Loop
         }
         else {
-            self.position = (new_row, new_col);
+            self.position = next;
+            self.visited_with_direction.insert((self.position, self.direction));
+            self.visited.insert(self.position);
+        }
+        LoopOrExit::Moving
+    }
+
+    // A move while sharing the grid: cells in `occupied` block like obstacles. A guard can
+    // revisit a state without looping when the others have moved, so loops aren't checked here.
+    fn move_among(&mut self, grid: &Grid<char>, occupied: &HashSet<(usize, usize)>) -> LoopOrExit {
+        let Some(next) = self.next_position(grid) else {
+            return LoopOrExit::Exit
+        };
+        if grid[next] == '#' || occupied.contains(&next) {
+            self.direction = self.turn_rule.turn(self.direction);
+        }
+        else {
+            self.position = next;
             self.visited_with_direction.insert((self.position, self.direction));
             self.visited.insert(self.position);
         }
@@ -104,6 +215,93 @@ impl Guard {
     }
 }
 
+#[derive(Debug, PartialEq)]
+struct GuardReport {
+    start: (usize, usize),
+    coverage: usize,
+    status: LoopOrExit,
+}
+
+#[derive(Debug, PartialEq)]
+struct PatrolReport {
+    guards: Vec<GuardReport>,
+    // Loop if any guard never leaves
+    status: LoopOrExit,
+}
+
+struct Patrol {
+    guards: Vec<Guard>,
+    guards_block: bool,
+}
+
+impl Patrol {
+    fn new(guards_block: bool) -> Patrol {
+        Patrol { guards: Vec::new(), guards_block }
+    }
+
+    fn from_grid(grid: &Grid<char>, turn_rule: TurnRule, guards_block: bool) -> Patrol {
+        let mut patrol = Patrol::new(guards_block);
+        for (position, direction) in find_guards(grid) {
+            patrol.add_guard(Guard::with_rule(position, direction, turn_rule));
+        }
+        patrol
+    }
+
+    fn add_guard(&mut self, guard: Guard) {
+        self.guards.push(guard);
+    }
+
+    fn run(&mut self, grid: &Grid<char>) -> PatrolReport {
+        let starts: Vec<(usize, usize)> = self.guards.iter().map(|guard| guard.position).collect();
+        let statuses = if self.guards_block {
+            self.run_together(grid)
+        } else {
+            self.guards.iter_mut().map(|guard| guard.move_until_left_or_looped(grid)).collect()
+        };
+        let status = if statuses.contains(&LoopOrExit::Loop) { LoopOrExit::Loop } else { LoopOrExit::Exit };
+        let guards = starts.into_iter()
+            .zip(&self.guards)
+            .zip(statuses)
+            .map(|((start, guard), status)| GuardReport { start, coverage: guard.visited.len(), status })
+            .collect();
+        PatrolReport { guards, status }
+    }
+
+    // Guards take turns to move. The patrol loops once every remaining guard is back
+    // in a position and direction they have all been in together before.
+    fn run_together(&mut self, grid: &Grid<char>) -> Vec<LoopOrExit> {
+        let mut statuses = vec![LoopOrExit::Moving; self.guards.len()];
+        let mut seen = HashSet::new();
+        loop {
+            let state: Vec<Option<((usize, usize), Direction)>> = self.guards.iter()
+                .zip(&statuses)
+                .map(|(guard, status)| (*status == LoopOrExit::Moving).then_some((guard.position, guard.direction)))
+                .collect();
+            if state.iter().all(Option::is_none) {
+                return statuses;
+            }
+            if !seen.insert(state) {
+                return statuses.into_iter()
+                    .map(|status| if status == LoopOrExit::Moving { LoopOrExit::Loop } else { status })
+                    .collect();
+            }
+            for idx in 0..self.guards.len() {
+                if statuses[idx] != LoopOrExit::Moving {
+                    continue;
+                }
+                let occupied: HashSet<(usize, usize)> = self.guards.iter()
+                    .zip(&statuses)
+                    .enumerate()
+                    .filter(|(other, (_, status))| *other != idx && **status == LoopOrExit::Moving)
+                    .map(|(_, (guard, _))| guard.position)
+                    .collect();
+                statuses[idx] = self.guards[idx].move_among(grid, &occupied);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
 fn will_loop(grid: &Grid<char>, position: (usize, usize), direction: Direction) -> bool {
     let mut guard = Guard::new(position, direction);
     let looped_or_left = guard.move_until_left_or_looped(grid);
@@ -113,74 +311,342 @@ fn will_loop(grid: &Grid<char>, position: (usize, usize), direction: Direction)
     false
 }
 
-// given a grid, add a # to each position and see if the guard will loop
-fn find_looping_positions(grid: &mut Grid<char>, guard_position: (usize,usize)) -> usize {
-    
-    // get all of the positions they will walk in the original walk
-    let mut guard = Guard::new(guard_position, Direction::UP);
-    guard.move_until_left_or_looped(grid);
-    let positions_walked = guard.visited.clone();
-    
-    
-    let mut looping_positions = 0;
-
-    let row_count = grid.rows();
-    let col_count = grid.cols();
-
-    for row_idx in 0..row_count {
-        for col_idx in 0..col_count {
-            if (row_idx, col_idx) == guard_position {
-                continue;
+const EXIT: u32 = u32::MAX;
+
+// Fixed size bitset, used for obstacles and visited (cell, direction) states
+#[derive(Clone)]
+struct BitSet {
+    words: Vec<u64>,
+}
+
+impl BitSet {
+    fn new(len: usize) -> BitSet {
+        BitSet { words: vec![0; len.div_ceil(64)] }
+    }
+
+    fn contains(&self, idx: usize) -> bool {
+        self.words[idx / 64] & (1 << (idx % 64)) != 0
+    }
+
+    // returns true if the bit was not already set
+    fn insert(&mut self, idx: usize) -> bool {
+        let was_set = self.contains(idx);
+        self.words[idx / 64] |= 1 << (idx % 64);
+        !was_set
+    }
+
+    fn remove(&mut self, idx: usize) {
+        self.words[idx / 64] &= !(1 << (idx % 64));
+    }
+}
+
+// For every cell and direction, the cell where the guard stops when walking that way:
+// the cell in front of the next obstacle, or EXIT if they walk off the grid.
+// Blocked cells keep the value they would have if they were open, so removing
+// an obstacle can be undone from its own entry.
+#[derive(Clone)]
+struct JumpTable {
+    rows: usize,
+    cols: usize,
+    blocked: BitSet,
+    stops: [Vec<u32>; 4],
+}
+
+impl JumpTable {
+    fn new(grid: &Grid<char>) -> JumpTable {
+        let (rows, cols) = grid.size();
+        let len = rows * cols;
+        let mut blocked = BitSet::new(len);
+        for ((row, col), cell) in grid.indexed_iter() {
+            if *cell == '#' {
+                blocked.insert(row * cols + col);
             }
-            if !positions_walked.contains(&(row_idx, col_idx)) {
-                continue;
+        }
+        let mut table = JumpTable {
+            rows,
+            cols,
+            blocked,
+            stops: std::array::from_fn(|_| vec![EXIT; len]),
+        };
+        let (up, right, down, left) = (0, 1, 2, 3);
+        // each stop depends on the cell in front of it, so fill from the edge being walked towards
+        for cell in 0..len {
+            table.stops[up][cell] = table.compute_stop(cell, up);
+            table.stops[left][cell] = table.compute_stop(cell, left);
+        }
+        for cell in (0..len).rev() {
+            table.stops[down][cell] = table.compute_stop(cell, down);
+            table.stops[right][cell] = table.compute_stop(cell, right);
+        }
+        table
+    }
+
+    fn len(&self) -> usize {
+        self.rows * self.cols
+    }
+
+    fn index(&self, (row, col): (usize, usize)) -> usize {
+        row * self.cols + col
+    }
+
+    fn step(&self, cell: usize, dir: usize) -> Option<usize> {
+        let direction = Direction::ALL[dir];
+        let row = (cell / self.cols).checked_add_signed(direction.d_row)?;
+        let col = (cell % self.cols).checked_add_signed(direction.d_col)?;
+        if row < self.rows && col < self.cols {
+            Some(row * self.cols + col)
+        } else {
+            None
+        }
+    }
+
+    fn compute_stop(&self, cell: usize, dir: usize) -> u32 {
+        match self.step(cell, dir) {
+            None => EXIT,
+            Some(next) if self.blocked.contains(next) => cell as u32,
+            Some(next) => self.stops[dir][next],
+        }
+    }
+
+    fn add_obstacle(&mut self, cell: usize) {
+        self.blocked.insert(cell);
+        self.update_around(cell);
+    }
+
+    fn remove_obstacle(&mut self, cell: usize) {
+        self.blocked.remove(cell);
+        self.update_around(cell);
+    }
+
+    // Only the cells that walk into `cell` can change: for each direction, follow the ray
+    // backwards from `cell` up to and including the next obstacle.
+    fn update_around(&mut self, cell: usize) {
+        for dir in 0..4 {
+            let behind = (dir + 2) % 4;
+            let mut current = self.step(cell, behind);
+            while let Some(c) = current {
+                self.stops[dir][c] = self.compute_stop(c, dir);
+                if self.blocked.contains(c) {
+                    break;
+                }
+                current = self.step(c, behind);
             }
-            if *grid.get(row_idx, col_idx).unwrap() == '#' {
-                continue;
+        }
+    }
+
+    fn position(&self, cell: usize) -> (usize, usize) {
+        (cell / self.cols, cell % self.cols)
+    }
+
+    // Jumps from stop to stop, returning the first (cell, direction) state seen twice,
+    // or None if the guard leaves the grid
+    fn loop_state_from(&self, start: usize, dir: usize, seen: &mut VisitedStates) -> Option<(usize, usize)> {
+        seen.clear();
+        let (mut cell, mut dir) = (start, dir);
+        loop {
+            let stop = self.stops[dir][cell];
+            if stop == EXIT {
+                return None;
             }
-            *grid.get_mut(row_idx, col_idx).unwrap() = '#';
-            if will_loop(grid, guard_position, Direction::UP) {
-                looping_positions += 1;
+            cell = stop as usize;
+            if !seen.insert(cell * 4 + dir) {
+                return Some((cell, dir));
             }
-            *grid.get_mut(row_idx, col_idx).unwrap() = '.';
+            dir = (dir + 1) % 4;
+        }
+    }
+
+    // A single move or turn, the way Guard::move_guard does it
+    fn advance(&self, cell: usize, dir: usize) -> Option<(usize, usize, bool)> {
+        let next = self.step(cell, dir)?;
+        if self.blocked.contains(next) {
+            Some((cell, (dir + 1) % 4, false))
+        } else {
+            Some((next, dir, true))
         }
     }
 
-    looping_positions
+    // Walks the loop through `state` cell by cell, marking every (cell, direction) on it.
+    // Returns the number of moves around the loop.
+    fn mark_loop(&self, state: (usize, usize), on_loop: &mut VisitedStates) -> usize {
+        on_loop.clear();
+        let (mut cell, mut dir) = state;
+        let mut moves = 0;
+        while on_loop.insert(cell * 4 + dir) {
+            let (next, next_dir, moved) = self.advance(cell, dir).expect("Guard left a loop");
+            moves += moved as usize;
+            (cell, dir) = (next, next_dir);
+        }
+        moves
+    }
+
+    // Number of moves from the start before the guard is on the marked loop
+    fn steps_until_loop(&self, start: usize, on_loop: &VisitedStates) -> usize {
+        let (mut cell, mut dir) = (start, 0);
+        let mut moves = 0;
+        while !on_loop.contains(cell * 4 + dir) {
+            let (next, next_dir, moved) = self.advance(cell, dir).expect("Guard left before looping");
+            moves += moved as usize;
+            (cell, dir) = (next, next_dir);
+        }
+        moves
+    }
 }
 
-fn find_looping_positions_parallel(grid_o:  Grid<char>, guard_position: (usize, usize)) -> usize {
-    let row_count = grid_o.rows();
-    let col_count = grid_o.cols();
-    let mut guard = Guard::new(guard_position, Direction::UP);
-    guard.move_until_left_or_looped(&grid_o);
-    let positions_walked = guard.visited.clone();
-    
-    (0..row_count)
+// Visited (cell, direction) states, remembering what was set so clearing is cheap
+struct VisitedStates {
+    bits: BitSet,
+    set: Vec<usize>,
+}
+
+impl VisitedStates {
+    fn new(cells: usize) -> VisitedStates {
+        VisitedStates { bits: BitSet::new(cells * 4), set: Vec::new() }
+    }
+
+    fn contains(&self, state: usize) -> bool {
+        self.bits.contains(state)
+    }
+
+    fn insert(&mut self, state: usize) -> bool {
+        let inserted = self.bits.insert(state);
+        if inserted {
+            self.set.push(state);
+        }
+        inserted
+    }
+
+    fn clear(&mut self) {
+        for state in self.set.drain(..) {
+            self.bits.remove(state);
+        }
+    }
+}
+
+// Every cell on the guard's original patrol (except the start) is a candidate obstruction.
+// Returns each with the cell and direction the guard had just before first entering it,
+// since the patrol up to that point is unaffected by the obstruction.
+fn candidate_obstructions(table: &JumpTable, start: usize) -> Vec<(usize, usize, usize)> {
+    let mut walked = BitSet::new(table.len());
+    let mut states = VisitedStates::new(table.len());
+    walked.insert(start);
+    let (mut cell, mut dir) = (start, 0);
+    let mut candidates = Vec::new();
+    while let Some(next) = table.step(cell, dir) {
+        if !states.insert(cell * 4 + dir) {
+            break;
+        }
+        if table.blocked.contains(next) {
+            dir = (dir + 1) % 4;
+            continue;
+        }
+        if walked.insert(next) {
+            candidates.push((next, cell, dir));
+        }
+        cell = next;
+    }
+    candidates
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct Obstruction {
+    position: (usize, usize),
+    // moves the guard takes to go once around the loop
+    loop_length: usize,
+    // moves from the start before the guard is on the loop
+    entry_step: usize,
+}
+
+fn check_obstruction(table: &mut JumpTable, seen: &mut VisitedStates, start: usize, (cell, from, dir): (usize, usize, usize)) -> Option<Obstruction> {
+    table.add_obstacle(cell);
+    let obstruction = table.loop_state_from(from, dir, seen).map(|state| {
+        let loop_length = table.mark_loop(state, seen);
+        Obstruction {
+            position: table.position(cell),
+            loop_length,
+            entry_step: table.steps_until_loop(start, seen),
+        }
+    });
+    table.remove_obstacle(cell);
+    obstruction
+}
+
+// given a grid, add a # to each position and see if the guard will loop
+fn find_looping_positions(grid: &Grid<char>, guard_position: (usize,usize)) -> Vec<Obstruction> {
+    let mut table = JumpTable::new(grid);
+    let mut seen = VisitedStates::new(table.len());
+    let start = table.index(guard_position);
+
+    let mut obstructions: Vec<Obstruction> = candidate_obstructions(&table, start)
+        .into_iter()
+        .filter_map(|candidate| check_obstruction(&mut table, &mut seen, start, candidate))
+        .collect();
+    obstructions.sort_by_key(|obstruction| obstruction.position);
+    obstructions
+}
+
+fn find_looping_positions_parallel(grid_o:  Grid<char>, guard_position: (usize, usize)) -> Vec<Obstruction> {
+    let table = JumpTable::new(&grid_o);
+    let start = table.index(guard_position);
+
+    let mut obstructions: Vec<Obstruction> = candidate_obstructions(&table, start)
         .into_par_iter()
-        .map(|row_idx| {
-            let mut local_looping_positions = 0;
-            let mut grid = grid_o.clone();
-            for col_idx in 0..col_count {
-                if (row_idx, col_idx) == guard_position {
-                    continue;
-                }
-                if *grid.get(row_idx, col_idx).unwrap() == '#' {
-                    continue;
-                }
-                if !positions_walked.contains(&(row_idx, col_idx)) {
-                    continue;
-                }
-                *grid.get_mut(row_idx, col_idx).unwrap() = '#';
-                if will_loop(&grid, guard_position, Direction::UP) {
-                    local_looping_positions += 1;
+        .map_init(
+            || (table.clone(), VisitedStates::new(table.len())),
+            |(table, seen), candidate| check_obstruction(table, seen, start, candidate),
+        )
+        .flatten()
+        .collect();
+    obstructions.sort_by_key(|obstruction| obstruction.position);
+    obstructions
+}
+
+// Draws the guard's patrol with an extra obstruction (O) in the puzzle's style:
+// | and - for vertical and horizontal moves, + where the guard turns or crosses its path
+fn render_patrol(grid: &Grid<char>, guard_position: (usize, usize), obstruction: (usize, usize)) -> String {
+    let mut patrol = grid.clone();
+    patrol[obstruction] = '#';
+    let mut drawing = grid.clone();
+    drawing[obstruction] = 'O';
+
+    let mut mark = |position: (usize, usize), direction: Direction| {
+        let line = if direction.d_row == 0 { '-' } else { '|' };
+        let cell = &mut drawing[position];
+        *cell = match *cell {
+            '^' => '^',
+            '.' => line,
+            existing if existing == line => line,
+            _ => '+',
+        };
+    };
+
+    let mut guard = Guard::new(guard_position, Direction::UP);
+    let mut turned = HashSet::new();
+    mark(guard.position, guard.direction);
+    loop {
+        let (position, direction) = (guard.position, guard.direction);
+        match guard.move_guard(&patrol) {
+            LoopOrExit::Moving if guard.direction != direction => {
+                // turning in place: both lines meet here, and a repeated turn means a loop
+                mark(position, guard.direction);
+                if !turned.insert((position, direction)) {
+                    break;
                 }
-                *grid.get_mut(row_idx, col_idx).unwrap() = '.';
             }
+            LoopOrExit::Moving => mark(guard.position, direction),
+            LoopOrExit::Loop | LoopOrExit::Exit => break,
+        }
+    }
+    print_grid(&drawing)
+}
 
-            local_looping_positions
-        })
-        .sum()
+fn print_grid(grid: &Grid<char>) -> String {
+    let mut result = String::new();
+    for row in grid.iter_rows() {
+        result.extend(row);
+        result.push('\n');
+    }
+    result
 }
 
 fn load_grid(input_file: &str) -> Result<grid::Grid<char>, io::Error> {
@@ -197,6 +663,13 @@ fn load_grid(input_file: &str) -> Result<grid::Grid<char>, io::Error> {
     Ok(grid)
 }
 
+// Every guard marker on the grid, in reading order
+fn find_guards(grid: &Grid<char>) -> Vec<((usize, usize), Direction)> {
+    grid.indexed_iter()
+        .filter_map(|(position, &ch)| Direction::from_marker(ch).map(|direction| (position, direction)))
+        .collect()
+}
+
 fn find_guard_position(grid: &Grid<char>) -> Option<(usize, usize)> {
     for (row_idx, mut row) in grid.iter_rows().enumerate() {
         if let Some(col_idx) = row.position(|&ch| ch == '^') {
@@ -243,12 +716,173 @@ mod tests {
         assert_eq!(will_loop(&grid, position, Direction::UP), true);
     }
 
+    #[test]
+    fn test_jump_table_matches_walk() {
+        let grid = load_grid("test_input.txt").expect("Failed to load grid");
+        let table = JumpTable::new(&grid);
+        assert_eq!(table.stops[0][table.index((6, 4))], table.index((1, 4)) as u32);
+        assert_eq!(table.stops[1][table.index((1, 4))], table.index((1, 8)) as u32);
+        assert_eq!(table.stops[3][table.index((0, 3))], EXIT);
+    }
+
+    #[test]
+    fn test_jump_table_obstacle_round_trip() {
+        let grid = load_grid("test_input.txt").expect("Failed to load grid");
+        let mut table = JumpTable::new(&grid);
+        let original = table.stops.clone();
+        let cell = table.index((3, 4));
+        table.add_obstacle(cell);
+        assert_eq!(table.stops[0][table.index((6, 4))], table.index((4, 4)) as u32);
+        assert_eq!(table.stops[2][table.index((0, 4))], table.index((2, 4)) as u32);
+        table.remove_obstacle(cell);
+        assert_eq!(table.stops, original);
+    }
+
+    #[test]
+    fn test_jump_table_agrees_with_guard() {
+        let mut grid = load_grid("test_input.txt").expect("Failed to load grid");
+        let position = find_guard_position(&grid).expect("No Guard Found");
+        let mut table = JumpTable::new(&grid);
+        let mut seen = VisitedStates::new(table.len());
+        for row in 0..grid.rows() {
+            for col in 0..grid.cols() {
+                if (row, col) == position || grid[(row, col)] == '#' {
+                    continue;
+                }
+                let cell = table.index((row, col));
+                table.add_obstacle(cell);
+                grid[(row, col)] = '#';
+                assert_eq!(
+                    table.loop_state_from(table.index(position), 0, &mut seen).is_some(),
+                    will_loop(&grid, position, Direction::UP),
+                    "obstacle at {:?}", (row, col)
+                );
+                grid[(row, col)] = '.';
+                table.remove_obstacle(cell);
+            }
+        }
+    }
+
+    #[test]
+    fn test_count_loops_sequential() {
+        let grid = load_grid("test_input.txt").expect("Failed to load grid");
+        let position = find_guard_position(&grid).expect("No Guard Found");
+        assert_eq!(find_looping_positions(&grid, position).len(), 6);
+    }
+
+    #[test]
+    fn test_list_obstructions() {
+        let grid = load_grid("test_input.txt").expect("Failed to load grid");
+        let position = find_guard_position(&grid).expect("No Guard Found");
+        let obstructions = find_looping_positions(&grid, position);
+        let positions: Vec<(usize, usize)> = obstructions.iter().map(|o| o.position).collect();
+        assert_eq!(positions, vec![(6, 3), (7, 6), (7, 7), (8, 1), (8, 3), (9, 7)]);
+        assert_eq!(obstructions[0], Obstruction { position: (6, 3), loop_length: 18, entry_step: 0 });
+        assert_eq!(obstructions, find_looping_positions_parallel(grid, position));
+    }
+
+    #[test]
+    fn test_render_patrol() {
+        let grid = load_grid("test_input.txt").expect("Failed to load grid");
+        let position = find_guard_position(&grid).expect("No Guard Found");
+        let expected = "\
+....#.....
+....+---+#
+....|...|.
+..#.|...|.
+....|..#|.
+....|...|.
+.#.O^---+.
+........#.
+#.........
+......#...
+";
+        assert_eq!(render_patrol(&grid, position, (6, 3)), expected);
+        let expected = "\
+....#.....
+....+---+#
+....|...|.
+..#.|...|.
+..+-+-+#|.
+..|.|.|.|.
+.#+-^-+-+.
+......O.#.
+#.........
+......#...
+";
+        assert_eq!(render_patrol(&grid, position, (7, 6)), expected);
+    }
+
+    #[test]
+    fn test_patrol_single_guard() {
+        let grid = load_grid("test_input.txt").expect("Failed to load grid");
+        let report = Patrol::from_grid(&grid, TurnRule::Right, false).run(&grid);
+        assert_eq!(report.guards, vec![GuardReport { start: (6, 4), coverage: 41, status: LoopOrExit::Exit }]);
+        assert_eq!(report.status, LoopOrExit::Exit);
+
+        let grid = load_grid("looping_grid.txt").expect("Failed to load grid");
+        let report = Patrol::from_grid(&grid, TurnRule::Right, true).run(&grid);
+        assert_eq!(report.status, LoopOrExit::Loop);
+    }
+
+    #[test]
+    fn test_patrol_turn_rules() {
+        let grid = load_grid("test_input.txt").expect("Failed to load grid");
+        // straight up to the # at (0, 4), then back down and off the bottom
+        let report = Patrol::from_grid(&grid, TurnRule::Reverse, false).run(&grid);
+        assert_eq!(report.guards[0].coverage, 9);
+        assert_eq!(report.status, LoopOrExit::Exit);
+
+        // turning left on a mirrored map walks the mirror image of the original patrol
+        let mut mirrored = Grid::new(0, 0);
+        for row in grid.iter_rows() {
+            mirrored.push_row(row.rev().copied().collect());
+        }
+        let report = Patrol::from_grid(&mirrored, TurnRule::Left, false).run(&mirrored);
+        assert_eq!(report.guards[0].coverage, 41);
+    }
+
+    #[test]
+    fn test_boxed_in_guard_loops() {
+        let corridor = Grid::from_vec(vec!['#', '>', '#'], 3);
+        let report = Patrol::from_grid(&corridor, TurnRule::Reverse, false).run(&corridor);
+        assert_eq!(report.guards, vec![GuardReport { start: (0, 1), coverage: 1, status: LoopOrExit::Loop }]);
+
+        let walled_in = Grid::from_vec(".#.#^#.#.".chars().collect(), 3);
+        for turn_rule in [TurnRule::Right, TurnRule::Left, TurnRule::Reverse] {
+            for guards_block in [false, true] {
+                let report = Patrol::from_grid(&walled_in, turn_rule, guards_block).run(&walled_in);
+                assert_eq!(report.status, LoopOrExit::Loop);
+            }
+        }
+    }
+
+    #[test]
+    fn test_patrol_multiple_guards() {
+        let grid = load_grid("multi_guard.txt").expect("Failed to load grid");
+        let guards = find_guards(&grid);
+        assert_eq!(guards, vec![((0, 1), Direction::RIGHT), ((0, 4), Direction::LEFT)]);
+
+        // walking through each other, both bounce off the # and leave on the left
+        let report = Patrol::from_grid(&grid, TurnRule::Reverse, false).run(&grid);
+        assert_eq!(report.guards.iter().map(|guard| guard.coverage).collect::<Vec<_>>(), vec![5, 5]);
+        assert_eq!(report.status, LoopOrExit::Exit);
+
+        // blocking each other, the first guard bounces straight back out
+        let report = Patrol::from_grid(&grid, TurnRule::Reverse, true).run(&grid);
+        assert_eq!(report.guards, vec![
+            GuardReport { start: (0, 1), coverage: 3, status: LoopOrExit::Exit },
+            GuardReport { start: (0, 4), coverage: 5, status: LoopOrExit::Exit },
+        ]);
+        assert_eq!(report.status, LoopOrExit::Exit);
+    }
+
     #[test]
     fn test_count_loops() {
         let input_file = "test_input.txt";
         let grid = load_grid(input_file).expect("Failed to load grid");
         let position = find_guard_position(&grid).expect("No Guard Found");
         let loops = find_looping_positions_parallel(grid, position);
-        assert_eq!(loops, 6);
+        assert_eq!(loops.len(), 6);
     }
 }
\ No newline at end of file
@@ -1,20 +1,35 @@
-use std::collections::VecDeque;
+use std::fmt;
+use std::fmt::Debug;
+use std::str::FromStr;
 use std::vec;
+use rayon::prelude::*;
 
 fn main() {
     let input_file = "input.txt";
-    let inputs = load_input(input_file);
-    let total:usize = inputs.iter()
-        .filter(|(array, target)| can_reach_target(array.clone(), *target, false))
-        .map(|(_, target)|target).sum();
+    let wide_inputs = load_wide_input(input_file);
+    let total = total_calibration(&wide_inputs, false).expect("Total overflowed");
     println!("Total Part1: {}", total);
-    let total:usize = inputs.iter()
-        .filter(|(array, target)| can_reach_target(array.clone(), *target, true))
-        .map(|(_, target)|target).sum();
+    let total = total_calibration(&wide_inputs, true).expect("Total overflowed");
     println!("Total Part2: {}", total);
+    if std::env::args().any(|arg| arg == "--show") {
+        let inputs = load_input(input_file);
+        for (array, target) in &inputs {
+            if let Some(equation) = find_equation(array, *target, PART2_OPERATORS) {
+                println!("{} = {}", target, equation);
+            }
+        }
+    }
 }
 
 fn load_input(p0: &str) -> Vec<(Vec<usize>,usize)> {
+    parse_input(p0)
+}
+
+fn load_wide_input(p0: &str) -> Vec<(Vec<u128>,u128)> {
+    parse_input(p0)
+}
+
+fn parse_input<T: FromStr>(p0: &str) -> Vec<(Vec<T>,T)> where T::Err: Debug {
     let mut inputs = vec::Vec::new();
     let file = std::fs::read_to_string(p0).unwrap();
     // line looks like "292: 11 6 16 20"
@@ -27,30 +42,165 @@ fn load_input(p0: &str) -> Vec<(Vec<usize>,usize)> {
     inputs
 }
 
+/// A binary operator that can sit between two operands. Equations are always
+/// evaluated left to right, so `apply` gets the running total and the next number.
+/// Returning `None` means the operator can't be used there (e.g. overflow).
+pub trait Operator {
+    fn symbol(&self) -> &str;
+    fn apply(&self, left: usize, right: usize) -> Option<usize>;
+}
+
+pub struct Add;
+pub struct Mul;
+pub struct Concat;
+pub struct Subtract;
+pub struct Divide;
+pub struct Xor;
+
+impl Operator for Add {
+    fn symbol(&self) -> &str { "+" }
+    fn apply(&self, left: usize, right: usize) -> Option<usize> { left.checked_add(right) }
+}
+
+impl Operator for Mul {
+    fn symbol(&self) -> &str { "*" }
+    fn apply(&self, left: usize, right: usize) -> Option<usize> { left.checked_mul(right) }
+}
+
+impl Operator for Concat {
+    fn symbol(&self) -> &str { "||" }
+    fn apply(&self, left: usize, right: usize) -> Option<usize> {
+        let shift = 10usize.checked_pow(right.checked_ilog10().unwrap_or(0) + 1)?;
+        left.checked_mul(shift)?.checked_add(right)
+    }
+}
+
+impl Operator for Subtract {
+    fn symbol(&self) -> &str { "-" }
+    fn apply(&self, left: usize, right: usize) -> Option<usize> { left.checked_sub(right) }
+}
+
+impl Operator for Divide {
+    fn symbol(&self) -> &str { "/" }
+    fn apply(&self, left: usize, right: usize) -> Option<usize> { left.checked_div(right) }
+}
+
+impl Operator for Xor {
+    fn symbol(&self) -> &str { "^" }
+    fn apply(&self, left: usize, right: usize) -> Option<usize> { Some(left ^ right) }
+}
+
+pub const PART1_OPERATORS: &[&dyn Operator] = &[&Add, &Mul];
+pub const PART2_OPERATORS: &[&dyn Operator] = &[&Add, &Mul, &Concat];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Equation {
+    pub operands: Vec<usize>,
+    pub operators: Vec<String>,
+}
+
+impl fmt::Display for Equation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.operands[0])?;
+        for (operator, operand) in self.operators.iter().zip(&self.operands[1..]) {
+            write!(f, " {} {}", operator, operand)?;
+        }
+        Ok(())
+    }
+}
+
 pub fn can_reach_target(array: Vec<usize>, target: usize, with_concat:bool) -> bool {
-    if array.is_empty() {
-        return false;
+    let operators = if with_concat { PART2_OPERATORS } else { PART1_OPERATORS };
+    find_equation(&array, target, operators).is_some()
+}
+
+/// The first equation (trying operators in the order given) that uses every number to hit the target
+pub fn find_equation(array: &[usize], target: usize, operators: &[&dyn Operator]) -> Option<Equation> {
+    let mut solutions = Vec::new();
+    if let Some(&first) = array.first() {
+        search(array, target, operators, false, first, &mut Vec::new(), &mut solutions);
     }
-    let mut queue = VecDeque::new();
-    queue.push_back((array[0], 1));
+    solutions.pop()
+}
 
-    while let Some((current_value, index)) = queue.pop_front() {
-        if current_value == target {
-            return true;
+/// Every equation that uses every number to hit the target
+pub fn find_all_equations(array: &[usize], target: usize, operators: &[&dyn Operator]) -> Vec<Equation> {
+    let mut solutions = Vec::new();
+    if let Some(&first) = array.first() {
+        search(array, target, operators, true, first, &mut Vec::new(), &mut solutions);
+    }
+    solutions
+}
+
+// Depth first over operator choices, carrying the running total of array[..=chosen.len()].
+// `chosen` holds indexes into `operators`. Returns true once the search can stop.
+fn search(array: &[usize], target: usize, operators: &[&dyn Operator], find_all: bool,
+          value: usize, chosen: &mut Vec<usize>, solutions: &mut Vec<Equation>) -> bool {
+    let index = chosen.len() + 1;
+    if index == array.len() {
+        if value != target {
+            return false;
         }
-        if index == array.len() {
+        solutions.push(Equation {
+            operands: array.to_vec(),
+            operators: chosen.iter().map(|&op| operators[op].symbol().to_string()).collect(),
+        });
+        return !find_all;
+    }
+    for (op, operator) in operators.iter().enumerate() {
+        let Some(next_value) = operator.apply(value, array[index]) else {
             continue;
-        }
-        let next_number = array[index];
-        queue.push_back((current_value + next_number, index + 1));
-        queue.push_back((current_value * next_number, index + 1));
-        if with_concat {
-            queue.push_back(((current_value.to_string() + &next_number.to_string()).parse().unwrap(), index + 1));
+        };
+        chosen.push(op);
+        let done = search(array, target, operators, find_all, next_value, chosen, solutions);
+        chosen.pop();
+        if done {
+            return true;
         }
     }
     false
 }
 
+/// Works back from the target instead of trying every operator going forwards: the last
+/// number can only have been added if the target is at least as big, multiplied if it
+/// divides the target, and concatenated if the target ends with its digits. Every step
+/// makes the target smaller, so nothing can overflow.
+pub fn can_reach_target_backwards(array: &[u128], target: u128, with_concat: bool) -> bool {
+    let Some((&last, rest)) = array.split_last() else {
+        return false;
+    };
+    if rest.is_empty() {
+        return last == target;
+    }
+    if target.checked_sub(last).is_some_and(|t| can_reach_target_backwards(rest, t, with_concat)) {
+        return true;
+    }
+    // multiplying by zero gives zero whatever came before
+    if last == 0 {
+        if target == 0 {
+            return true;
+        }
+    } else if target.is_multiple_of(last) && can_reach_target_backwards(rest, target / last, with_concat) {
+        return true;
+    }
+    with_concat && strip_suffix(target, last).is_some_and(|t| can_reach_target_backwards(rest, t, with_concat))
+}
+
+// target with the digits of suffix removed from its end, if it ends with them
+fn strip_suffix(target: u128, suffix: u128) -> Option<u128> {
+    let shift = 10u128.checked_pow(suffix.checked_ilog10().unwrap_or(0) + 1)?;
+    (target % shift == suffix).then_some(target / shift)
+}
+
+/// Sum of the targets that can be reached, solving the equations in parallel.
+/// Returns `None` if the sum doesn't fit in a u128.
+pub fn total_calibration(inputs: &[(Vec<u128>, u128)], with_concat: bool) -> Option<u128> {
+    inputs.par_iter()
+        .filter(|(array, target)| can_reach_target_backwards(array, *target, with_concat))
+        .map(|(_, target)| Some(*target))
+        .try_reduce(|| 0, |a, b| a.checked_add(b))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -98,4 +248,86 @@ mod tests {
         assert_eq!(total, 11387);
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_find_equation() {
+        let equation = find_equation(&[81, 40, 27], 3267, PART1_OPERATORS).unwrap();
+        assert_eq!(equation.to_string(), "81 + 40 * 27");
+        let equation = find_equation(&[15, 6], 156, PART2_OPERATORS).unwrap();
+        assert_eq!(equation.to_string(), "15 || 6");
+        assert_eq!(find_equation(&[17, 5], 83, PART2_OPERATORS), None);
+    }
+
+    #[test]
+    fn test_find_all_equations() {
+        let equations: Vec<String> = find_all_equations(&[81, 40, 27], 3267, PART1_OPERATORS)
+            .iter().map(|equation| equation.to_string()).collect();
+        assert_eq!(equations, vec!["81 + 40 * 27", "81 * 40 + 27"]);
+    }
+
+    #[test]
+    fn test_extra_builtin_operators() {
+        let operators: &[&dyn Operator] = &[&Subtract, &Divide, &Xor];
+        assert_eq!(find_equation(&[20, 5, 3], 7, operators).unwrap().to_string(), "20 / 5 ^ 3");
+        assert_eq!(find_equation(&[20, 5, 3], 12, &[&Subtract]).unwrap().to_string(), "20 - 5 - 3");
+        // no negative intermediate results
+        assert_eq!(find_equation(&[5, 20, 30], 15, &[&Subtract]), None);
+        assert_eq!(find_equation(&[5, 0], 0, &[&Divide]), None);
+    }
+
+    struct Max;
+
+    impl Operator for Max {
+        fn symbol(&self) -> &str { "max" }
+        fn apply(&self, left: usize, right: usize) -> Option<usize> { Some(left.max(right)) }
+    }
+
+    #[test]
+    fn test_custom_operator() {
+        let operators: &[&dyn Operator] = &[&Add, &Max];
+        let equation = find_equation(&[3, 10, 2], 12, operators).unwrap();
+        assert_eq!(equation.to_string(), "3 max 10 + 2");
+    }
+
+    #[test]
+    fn test_backwards_matches_forwards() {
+        let inputs = load_input("test_input.txt");
+        let wide_inputs = load_wide_input("test_input.txt");
+        for ((array, target), (wide_array, wide_target)) in inputs.iter().zip(&wide_inputs) {
+            for with_concat in [false, true] {
+                assert_eq!(
+                    can_reach_target_backwards(wide_array, *wide_target, with_concat),
+                    can_reach_target(array.clone(), *target, with_concat),
+                    "{}: {:?}", target, array
+                );
+            }
+        }
+        assert_eq!(total_calibration(&wide_inputs, false), Some(3749));
+        assert_eq!(total_calibration(&wide_inputs, true), Some(11387));
+    }
+
+    #[test]
+    fn test_backwards_zeros() {
+        assert!(can_reach_target_backwards(&[5, 3, 0], 0, false));
+        assert!(can_reach_target_backwards(&[5, 0], 50, true));
+        assert!(!can_reach_target_backwards(&[5, 0], 1, true));
+    }
+
+    #[test]
+    fn test_backwards_long_operand_list() {
+        // alternate * and + over 30 numbers; going forwards this is 3^29 states
+        let array: Vec<u128> = (0..30).map(|i| [17, 23, 41, 7][i % 4]).collect();
+        let target = array[1..].iter().enumerate()
+            .fold(array[0], |acc, (i, &x)| if i % 2 == 0 { acc * x } else { acc + x });
+        assert!(can_reach_target_backwards(&array, target, true));
+        assert!(!can_reach_target_backwards(&array, target + 1, true));
+    }
+
+    #[test]
+    fn test_backwards_no_overflow() {
+        let big = u64::MAX as u128;
+        assert!(can_reach_target_backwards(&[big, big, 2], big * big + 2, false));
+        // u128::MAX * 2 wraps round to u128::MAX - 1, which must not count as a match
+        assert!(!can_reach_target_backwards(&[u128::MAX, 2], u128::MAX - 1, true));
+        assert_eq!(total_calibration(&[(vec![u128::MAX], u128::MAX), (vec![1], 1)], false), None);
+    }
+}
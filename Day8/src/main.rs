@@ -1,8 +1,10 @@
 use std::collections::{HashMap, HashSet};
+use std::error::Error;
 use std::fs::File;
 use std::io;
 use std::io::BufRead;
 use grid::*;
+use image::{ImageBuffer, Rgba, RgbaImage};
 use itertools::Itertools;
 use num::integer::gcd;
 
@@ -12,6 +14,7 @@ type AntennaSet = HashSet<Antenna>;
 
 type Antinode = (isize, isize);
 type AntinodeSet = HashSet<Antinode>;
+type AntennaPair = (Antenna, Antenna);
 
 fn main() {
     let input_file = "input.txt";
@@ -21,6 +24,34 @@ fn main() {
     println!("Antinodes: {:?}", antinodes.len());
     let antilines = find_antilines(&grid, &antennas);
     println!("Antilines: {:?}", antilines.len());
+    // --bounds=plane, --bounds=box:r,c,r,c or --bounds=polygon:r,c;r,c;... instead of the map
+    let bounds = std::env::args()
+        .find_map(|arg| arg.strip_prefix("--bounds=").map(|bounds| bounds.parse().unwrap()))
+        .unwrap_or_else(|| Bounds::of_grid(&grid));
+    let report = ResonanceModel::new(vec![Harmonic::Multiple(2), Harmonic::Ratio(1, 2)])
+        .analyse(&bounds, &antennas)
+        .unwrap();
+    for (frequency, count) in report.counts().iter().sorted() {
+        println!("{}: {} antinodes", frequency, count);
+    }
+    for (antinode, frequencies) in report.overlaps().iter().sorted() {
+        for frequency in frequencies {
+            println!("{:?} shared by {} from {:?}", antinode, frequency, report.sources(*frequency, *antinode));
+        }
+    }
+    // --render[=frequency] prints the map with antinodes and resonance lines, --png=file saves it
+    let args: Vec<String> = std::env::args().collect();
+    let options = RenderOptions {
+        frequency: args.iter().find_map(|arg| arg.strip_prefix("--render=")).and_then(|f| f.chars().next()),
+        lines: true,
+        colour: true,
+    };
+    if args.iter().any(|arg| arg.starts_with("--render")) {
+        print!("{}", render_map(&grid, &report, &options));
+    }
+    if let Some(path) = args.iter().find_map(|arg| arg.strip_prefix("--png=")) {
+        render_png(&grid, &report, &options, 8, path).unwrap();
+    }
 }
 
 pub fn print_grid(grid:Grid<char>) -> String {
@@ -66,60 +97,354 @@ fn find_antennas(grid: &Grid<char>) -> HashMap<Frequency, AntennaSet> {
 }
 
 fn find_antinodes(grid: &Grid<char>, antenna_set: &HashMap<Frequency, AntennaSet>) -> AntinodeSet {
-    let mut antinodes = AntinodeSet::new();
-    // iterate over antenna_set
-    antenna_set.iter().for_each(|(_, antenna_set)| {
-        // for each pair of antennas
-        for pair in antenna_set.iter().combinations(2) {
-            let (a, b) = (pair[0], pair[1]);
-            let (dx, dy) = (b.0 - a.0, b.1  - a.1 );
-
-            // Calculate antinodes
-            let antinode1 = (b.0 + dx, b.1 + dy);
-            let antinode2 = (a.0 - dx, a.1 - dy);
-            // insert the antinodes into the set if they are inside the grid bounds
-            if grid.get(antinode1.0 as usize, antinode1.1 as usize).is_some() {
-                antinodes.insert(antinode1);
+    ResonanceModel::new(vec![Harmonic::Multiple(2)])
+        .analyse(&Bounds::of_grid(grid), antenna_set)
+        .expect("A grid is bounded")
+        .all_antinodes()
+}
+
+fn find_antilines(grid: &Grid<char>, antenna_set: &HashMap<Frequency, AntennaSet>) -> AntinodeSet {
+    ResonanceModel::new(vec![Harmonic::Line])
+        .analyse(&Bounds::of_grid(grid), antenna_set)
+        .expect("A grid is bounded")
+        .all_antinodes()
+}
+
+// The area antinodes are counted in. Corners and vertices are (row, col) and inclusive.
+#[derive(Debug, Clone, PartialEq)]
+enum Bounds {
+    Box { min: Antinode, max: Antinode },
+    // cells inside or on the edge of the polygon through these vertices
+    Polygon(Vec<Antinode>),
+    // everywhere; only harmonics with a finite number of antinodes make sense here
+    Plane,
+}
+
+impl Bounds {
+    fn of_grid(grid: &Grid<char>) -> Bounds {
+        let (rows, cols) = grid.size();
+        Bounds::Box { min: (0, 0), max: (rows as isize - 1, cols as isize - 1) }
+    }
+
+    fn contains(&self, (row, col): Antinode) -> bool {
+        match self {
+            Bounds::Box { min, max } => (min.0..=max.0).contains(&row) && (min.1..=max.1).contains(&col),
+            Bounds::Polygon(vertices) => polygon_contains(vertices, (row, col)),
+            Bounds::Plane => true,
+        }
+    }
+
+    fn bounding_box(&self) -> Option<(Antinode, Antinode)> {
+        match self {
+            Bounds::Box { min, max } => Some((*min, *max)),
+            Bounds::Polygon(vertices) => {
+                let (min_row, max_row) = vertices.iter().map(|v| v.0).minmax().into_option()?;
+                let (min_col, max_col) = vertices.iter().map(|v| v.1).minmax().into_option()?;
+                Some(((min_row, min_col), (max_row, max_col)))
             }
-            if grid.get(antinode2.0 as usize, antinode2.1 as usize).is_some() {
-                antinodes.insert(antinode2);
+            Bounds::Plane => None,
+        }
+    }
+}
+
+impl std::str::FromStr for Bounds {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse_points = |points: &str| -> Result<Vec<Antinode>, String> {
+            points.split(';')
+                .map(|point| {
+                    let (row, col) = point.split_once(',').ok_or(format!("Bad point: {}", point))?;
+                    Ok((row.trim().parse().map_err(|_| format!("Bad row: {}", row))?,
+                        col.trim().parse().map_err(|_| format!("Bad col: {}", col))?))
+                })
+                .collect()
+        };
+        match s.split_once(':') {
+            None if s == "plane" => Ok(Bounds::Plane),
+            Some(("box", corners)) => {
+                let numbers = corners.split(',')
+                    .map(|n| n.trim().parse().map_err(|_| format!("Bad number: {}", n)))
+                    .collect::<Result<Vec<isize>, String>>()?;
+                match numbers[..] {
+                    [min_row, min_col, max_row, max_col] => Ok(Bounds::Box { min: (min_row, min_col), max: (max_row, max_col) }),
+                    _ => Err(format!("Expected two corners: {}", corners)),
+                }
             }
+            Some(("polygon", vertices)) => Ok(Bounds::Polygon(parse_points(vertices)?)),
+            _ => Err(format!("Unknown bounds: {}", s)),
+        }
+    }
+}
+
+// Even-odd rule, with points on an edge counting as inside. Worked in i128 so the
+// cross products can't overflow.
+fn polygon_contains(vertices: &[Antinode], (row, col): Antinode) -> bool {
+    let (row, col) = (row as i128, col as i128);
+    let mut inside = false;
+    for (&(r1, c1), &(r2, c2)) in vertices.iter().circular_tuple_windows() {
+        let (r1, c1, r2, c2) = (r1 as i128, c1 as i128, r2 as i128, c2 as i128);
+        let cross = (r2 - r1) * (col - c1) - (c2 - c1) * (row - r1);
+        if cross == 0 && (r1.min(r2)..=r1.max(r2)).contains(&row) && (c1.min(c2)..=c1.max(c2)).contains(&col) {
+            return true;
+        }
+        if (r1 > row) != (r2 > row) {
+            // is the point left of where the edge crosses its row?
+            let (lhs, rhs) = ((col - c1) * (r2 - r1), (row - r1) * (c2 - c1));
+            if (r2 > r1 && lhs < rhs) || (r2 < r1 && lhs > rhs) {
+                inside = !inside;
+            }
+        }
+    }
+    inside
+}
+
+// p + k·d, or None if it doesn't fit in an isize
+fn offset(p: Antinode, (d_row, d_col): (isize, isize), k: isize) -> Option<Antinode> {
+    Some((
+        p.0.checked_add(k.checked_mul(d_row)?)?,
+        p.1.checked_add(k.checked_mul(d_col)?)?,
+    ))
+}
+
+// Where a pair of antennas a, b resonates, in terms of d = b - a
+#[derive(Debug, Clone, PartialEq)]
+enum Harmonic {
+    // a + k·d and b - k·d, so k = 2 gives the first antinodes beyond each antenna
+    Multiple(isize),
+    // the points dividing a–b in the ratio m:n from either end, where they land on a cell
+    Ratio(isize, isize),
+    // every cell on the line through both antennas
+    Line,
+}
+
+struct ResonanceModel {
+    harmonics: Vec<Harmonic>,
+}
+
+impl ResonanceModel {
+    fn new(harmonics: Vec<Harmonic>) -> ResonanceModel {
+        ResonanceModel { harmonics }
+    }
+
+    fn pair_antinodes(&self, bounds: &Bounds, a: Antenna, b: Antenna) -> Result<Vec<Antinode>, String> {
+        let Some(d) = b.0.checked_sub(a.0).zip(b.1.checked_sub(a.1)) else {
+            return Ok(Vec::new());
         };
-    });
-    antinodes
+        let mut antinodes = Vec::new();
+        for harmonic in &self.harmonics {
+            match *harmonic {
+                Harmonic::Multiple(k) => {
+                    antinodes.extend(offset(a, d, k));
+                    antinodes.extend(k.checked_neg().and_then(|k| offset(b, d, k)));
+                }
+                Harmonic::Ratio(m, n) => {
+                    for (m, n) in [(m, n), (n, m)] {
+                        let Some(scaled) = m.checked_mul(d.0).zip(m.checked_mul(d.1)) else {
+                            continue;
+                        };
+                        let total = m.checked_add(n).unwrap_or(0);
+                        if total != 0 && scaled.0 % total == 0 && scaled.1 % total == 0 {
+                            antinodes.extend(offset(a, (scaled.0 / total, scaled.1 / total), 1));
+                        }
+                    }
+                }
+                Harmonic::Line => {
+                    let Some((min, max)) = bounds.bounding_box() else {
+                        return Err("A whole line has infinitely many antinodes in an unbounded plane".to_string());
+                    };
+                    let in_box = |p: Antinode| (min.0..=max.0).contains(&p.0) && (min.1..=max.1).contains(&p.1);
+                    let step = gcd(d.0, d.1);
+                    let step = (d.0 / step, d.1 / step);
+                    for k in [1, -1] {
+                        let mut current = Some(a);
+                        while let Some(point) = current.filter(|&p| in_box(p)) {
+                            antinodes.push(point);
+                            current = offset(point, step, k);
+                        }
+                    }
+                }
+            }
+        }
+        antinodes.retain(|&antinode| bounds.contains(antinode));
+        Ok(antinodes)
+    }
+
+    fn analyse(&self, bounds: &Bounds, antenna_set: &HashMap<Frequency, AntennaSet>) -> Result<ResonanceReport, String> {
+        let mut by_frequency = HashMap::new();
+        for (&frequency, antennas) in antenna_set {
+            let mut antinodes: HashMap<Antinode, Vec<AntennaPair>> = HashMap::new();
+            for pair in antennas.iter().sorted().combinations(2) {
+                let (a, b) = (*pair[0], *pair[1]);
+                for antinode in self.pair_antinodes(bounds, a, b)? {
+                    let sources = antinodes.entry(antinode).or_default();
+                    if !sources.contains(&(a, b)) {
+                        sources.push((a, b));
+                    }
+                }
+            }
+            by_frequency.insert(frequency, antinodes);
+        }
+        Ok(ResonanceReport { by_frequency })
+    }
 }
 
-fn find_antilines(grid: &Grid<char>, antenna_set: &HashMap<Frequency, AntennaSet>) -> AntinodeSet {
-    let mut antinodes = AntinodeSet::new();
-
-    // Iterate over each frequency and its corresponding antenna set
-    for (_, antennas) in antenna_set {
-        for pair in antennas.iter().combinations(2) {
-            let (a, b) = (pair[0], pair[1]);
-            let (dc, dy) = (b.0 - a.0, b.1 - a.1);
-            let step = gcd(dc.abs(), dy.abs());
-            let (step_x, step_y) = (dc / step, dy / step);
-
-            // Step from a to b and beyond
-            let mut current_x = a.0;
-            let mut current_y = a.1;
-            while grid.get(current_x as usize, current_y as usize).is_some() {
-                antinodes.insert((current_x, current_y));
-                current_x += step_x;
-                current_y += step_y;
+// Antinodes kept apart by frequency, with the antenna pairs that produced each one
+struct ResonanceReport {
+    by_frequency: HashMap<Frequency, HashMap<Antinode, Vec<AntennaPair>>>,
+}
+
+impl ResonanceReport {
+    fn counts(&self) -> HashMap<Frequency, usize> {
+        self.by_frequency.iter()
+            .map(|(&frequency, antinodes)| (frequency, antinodes.len()))
+            .collect()
+    }
+
+    fn all_antinodes(&self) -> AntinodeSet {
+        self.by_frequency.values().flat_map(|antinodes| antinodes.keys().copied()).collect()
+    }
+
+    // antinodes produced by more than one frequency, and which ones
+    fn overlaps(&self) -> HashMap<Antinode, Vec<Frequency>> {
+        let mut frequencies: HashMap<Antinode, Vec<Frequency>> = HashMap::new();
+        for (&frequency, antinodes) in &self.by_frequency {
+            for &antinode in antinodes.keys() {
+                frequencies.entry(antinode).or_default().push(frequency);
             }
+        }
+        frequencies.retain(|_, frequencies| frequencies.len() > 1);
+        frequencies.values_mut().for_each(|frequencies| frequencies.sort());
+        frequencies
+    }
 
-            // Step from b to a and beyond
-            let mut current_x = b.0;
-            let mut current_y = b.1;
-            while grid.get(current_x as usize, current_y as usize).is_some() {
-                antinodes.insert((current_x, current_y));
-                current_x -= step_x;
-                current_y -= step_y;
+    fn sources(&self, frequency: Frequency, antinode: Antinode) -> &[AntennaPair] {
+        self.by_frequency.get(&frequency)
+            .and_then(|antinodes| antinodes.get(&antinode))
+            .map_or(&[], |sources| sources.as_slice())
+    }
+}
+
+
+#[derive(Debug, Default)]
+struct RenderOptions {
+    // only show this frequency's antennas and antinodes
+    frequency: Option<Frequency>,
+    // draw the lines through each pair of antennas
+    lines: bool,
+    // ANSI colour per frequency in text output
+    colour: bool,
+}
+
+const PALETTE: [(u8, [u8; 3]); 6] = [
+    (31, [220, 50, 47]),
+    (32, [133, 153, 0]),
+    (33, [181, 137, 0]),
+    (34, [38, 139, 210]),
+    (35, [211, 54, 130]),
+    (36, [42, 161, 152]),
+];
+
+// Each cell of the map as the character to draw and the frequency it belongs to.
+// Antennas are drawn over antinodes, and antinodes over resonance lines.
+fn layer_map(grid: &Grid<char>, report: &ResonanceReport, options: &RenderOptions) -> Grid<(char, Option<Frequency>)> {
+    let (rows, cols) = grid.size();
+    let mut layers = Grid::init(rows, cols, ('.', None));
+    let shown = |frequency: &Frequency| options.frequency.is_none_or(|f| f == *frequency);
+    let antennas = find_antennas(grid);
+    let bounds = Bounds::of_grid(grid);
+
+    if options.lines {
+        let line = ResonanceModel::new(vec![Harmonic::Line]);
+        for (&frequency, antennas) in antennas.iter().filter(|(f, _)| shown(f)).sorted_by_key(|(f, _)| **f) {
+            for pair in antennas.iter().sorted().combinations(2) {
+                let (a, b) = (*pair[0], *pair[1]);
+                let symbol = match (b.0 - a.0, b.1 - a.1) {
+                    (0, _) => '-',
+                    (_, 0) => '|',
+                    (d_row, d_col) if d_row == d_col => '\\',
+                    (d_row, d_col) if d_row == -d_col => '/',
+                    _ => '~',
+                };
+                for (row, col) in line.pair_antinodes(&bounds, a, b).expect("A grid is bounded") {
+                    let cell = &mut layers[(row as usize, col as usize)];
+                    *cell = match cell.0 {
+                        '.' => (symbol, Some(frequency)),
+                        existing if existing == symbol => *cell,
+                        _ => ('*', cell.1),
+                    };
+                }
+            }
+        }
+    }
+    for (&frequency, antinodes) in report.by_frequency.iter().filter(|(f, _)| shown(f)).sorted_by_key(|(f, _)| **f) {
+        // reports analysed with wider bounds than the grid have antinodes we can't draw
+        for &(row, col) in antinodes.keys().filter(|&&antinode| bounds.contains(antinode)) {
+            let cell = &mut layers[(row as usize, col as usize)];
+            if cell.0 != '#' {
+                *cell = ('#', Some(frequency));
             }
         }
     }
-    antinodes
+    for (&frequency, antennas) in antennas.iter().filter(|(f, _)| shown(f)) {
+        for &(row, col) in antennas {
+            layers[(row as usize, col as usize)] = (frequency, Some(frequency));
+        }
+    }
+    layers
+}
+
+// Frequencies in sorted order pick their colour from the palette in turn
+fn colour_index(grid: &Grid<char>) -> HashMap<Frequency, usize> {
+    find_antennas(grid).into_keys()
+        .sorted()
+        .enumerate()
+        .map(|(idx, frequency)| (frequency, idx % PALETTE.len()))
+        .collect()
+}
+
+fn render_map(grid: &Grid<char>, report: &ResonanceReport, options: &RenderOptions) -> String {
+    let colours = colour_index(grid);
+    let mut result = String::new();
+    for row in layer_map(grid, report, options).iter_rows() {
+        for &(symbol, frequency) in row {
+            match frequency.filter(|_| options.colour) {
+                Some(frequency) => {
+                    let code = PALETTE[colours[&frequency]].0;
+                    result.push_str(&format!("\x1b[{}m{}\x1b[0m", code, symbol));
+                }
+                None => result.push(symbol),
+            }
+        }
+        result.push('\n');
+    }
+    result
+}
+
+// Each cell becomes a scale × scale block: antennas in their frequency's colour,
+// antinodes a lighter shade of it and resonance lines a darker one
+fn render_png(grid: &Grid<char>, report: &ResonanceReport, options: &RenderOptions, scale: u32, path: &str) -> Result<(), Box<dyn Error>> {
+    let colours = colour_index(grid);
+    let (rows, cols) = grid.size();
+    let mut img: RgbaImage = ImageBuffer::from_pixel(cols as u32 * scale, rows as u32 * scale, Rgba([0, 0, 0, 255]));
+    for ((row, col), &(symbol, frequency)) in layer_map(grid, report, options).indexed_iter() {
+        let Some(frequency) = frequency else {
+            continue;
+        };
+        let [r, g, b] = PALETTE[colours[&frequency]].1;
+        let colour = match symbol {
+            '#' => Rgba([r / 2 + 128, g / 2 + 128, b / 2 + 128, 255]),
+            _ if symbol == frequency => Rgba([r, g, b, 255]),
+            _ => Rgba([r / 3, g / 3, b / 3, 255]),
+        };
+        for y in 0..scale {
+            for x in 0..scale {
+                img.put_pixel(col as u32 * scale + x, row as u32 * scale + y, colour);
+            }
+        }
+    }
+    img.save(path)?;
+    Ok(())
 }
 
 
@@ -213,5 +538,191 @@ mod tests {
         assert_eq!(antinodes.len(), 9);
     }
 
+    #[test]
+    fn test_antinodes_per_frequency() {
+        let grid = load_grid("test_input.txt").unwrap();
+        let antennas = find_antennas(&grid);
+        let report = ResonanceModel::new(vec![Harmonic::Multiple(2)]).analyse(&Bounds::of_grid(&grid), &antennas).unwrap();
+        assert_eq!(report.counts(), HashMap::from([('0', 10), ('A', 5)]));
+        assert_eq!(report.all_antinodes().len(), 14);
+        assert_eq!(report.overlaps(), HashMap::from([((1, 3), vec!['0', 'A'])]));
+        assert_eq!(report.sources('A', (1, 3)), &[((5, 6), (9, 9))]);
+        assert!(report.sources('b', (1, 3)).is_empty());
+    }
 
-}
\ No newline at end of file
+    fn antinodes_for(report: &ResonanceReport, frequency: Frequency) -> AntinodeSet {
+        report.by_frequency[&frequency].keys().copied().collect()
+    }
+
+    #[test]
+    fn test_higher_harmonics() {
+        let mut grid = Grid::new(10, 10);
+        grid.fill('.');
+        *grid.get_mut(0, 0).unwrap() = 'a';
+        *grid.get_mut(1, 1).unwrap() = 'a';
+        let antennas = find_antennas(&grid);
+        let report = ResonanceModel::new(vec![Harmonic::Multiple(3), Harmonic::Multiple(5)]).analyse(&Bounds::of_grid(&grid), &antennas).unwrap();
+        assert_eq!(antinodes_for(&report, 'a'), AntinodeSet::from([(3, 3), (5, 5)]));
+    }
+
+    #[test]
+    fn test_ratio_harmonics() {
+        let mut grid = Grid::new(10, 10);
+        grid.fill('.');
+        *grid.get_mut(0, 0).unwrap() = 'a';
+        *grid.get_mut(3, 6).unwrap() = 'a';
+        let antennas = find_antennas(&grid);
+        let report = ResonanceModel::new(vec![Harmonic::Ratio(1, 2)]).analyse(&Bounds::of_grid(&grid), &antennas).unwrap();
+        assert_eq!(antinodes_for(&report, 'a'), AntinodeSet::from([(1, 2), (2, 4)]));
+        // 1:1 would be half way, which isn't on a cell
+        let report = ResonanceModel::new(vec![Harmonic::Ratio(1, 1)]).analyse(&Bounds::of_grid(&grid), &antennas).unwrap();
+        assert!(antinodes_for(&report, 'a').is_empty());
+    }
+
+    fn antennas_at(positions: &[Antenna]) -> HashMap<Frequency, AntennaSet> {
+        HashMap::from([('a', positions.iter().copied().collect())])
+    }
+
+    #[test]
+    fn test_bounds() {
+        let grid_bounds = Bounds::Box { min: (0, 0), max: (9, 9) };
+        assert!(grid_bounds.contains((0, 9)));
+        assert!(!grid_bounds.contains((-1, 0)));
+        assert!(!grid_bounds.contains((0, 10)));
+
+        let triangle = Bounds::Polygon(vec![(0, 0), (0, 10), (10, 0)]);
+        assert!(triangle.contains((2, 2)));
+        assert!(triangle.contains((5, 5)));
+        assert!(triangle.contains((0, 4)));
+        assert!(!triangle.contains((6, 6)));
+        assert!(!triangle.contains((-1, 2)));
+        assert_eq!(triangle.bounding_box(), Some(((0, 0), (10, 10))));
+        assert!(Bounds::Plane.contains((isize::MIN, isize::MAX)));
+    }
+
+    #[test]
+    fn test_parse_bounds() {
+        assert_eq!("plane".parse(), Ok(Bounds::Plane));
+        assert_eq!("box:-2,-3,12,13".parse(), Ok(Bounds::Box { min: (-2, -3), max: (12, 13) }));
+        assert_eq!("polygon:0,0;0,10;10,0".parse(), Ok(Bounds::Polygon(vec![(0, 0), (0, 10), (10, 0)])));
+        assert!("box:1,2,3".parse::<Bounds>().is_err());
+        assert!("circle:1,2".parse::<Bounds>().is_err());
+    }
+
+    #[test]
+    fn test_antennas_near_the_edge() {
+        let antennas = antennas_at(&[(0, 0), (1, 1)]);
+        let model = ResonanceModel::new(vec![Harmonic::Multiple(2)]);
+        let report = model.analyse(&Bounds::Box { min: (0, 0), max: (9, 9) }, &antennas).unwrap();
+        assert_eq!(report.all_antinodes(), AntinodeSet::from([(2, 2)]));
+        let report = model.analyse(&Bounds::Plane, &antennas).unwrap();
+        assert_eq!(report.all_antinodes(), AntinodeSet::from([(2, 2), (-1, -1)]));
+    }
+
+    #[test]
+    fn test_polygon_site() {
+        let antennas = antennas_at(&[(2, 2), (2, 4)]);
+        let triangle = Bounds::Polygon(vec![(0, 0), (0, 10), (10, 0)]);
+        let report = ResonanceModel::new(vec![Harmonic::Multiple(2), Harmonic::Multiple(4)]).analyse(&triangle, &antennas).unwrap();
+        // (2, 10) is past the diagonal edge and (2, -4) is off the left
+        assert_eq!(report.all_antinodes(), AntinodeSet::from([(2, 6), (2, 0)]));
+        let report = ResonanceModel::new(vec![Harmonic::Line]).analyse(&triangle, &antennas).unwrap();
+        assert_eq!(report.all_antinodes().len(), 9);
+    }
+
+    #[test]
+    fn test_unbounded_plane_needs_finite_harmonics() {
+        let antennas = antennas_at(&[(0, 0), (1, 1)]);
+        assert!(ResonanceModel::new(vec![Harmonic::Line]).analyse(&Bounds::Plane, &antennas).is_err());
+    }
+
+    #[test]
+    fn test_no_overflow() {
+        let antennas = antennas_at(&[(isize::MAX - 1, 0), (isize::MAX, 1)]);
+        let report = ResonanceModel::new(vec![Harmonic::Multiple(2), Harmonic::Ratio(isize::MAX, 1)])
+            .analyse(&Bounds::Plane, &antennas)
+            .unwrap();
+        assert_eq!(report.all_antinodes(), AntinodeSet::from([(isize::MAX - 2, -1)]));
+    }
+
+    #[test]
+    fn test_render_antinodes() {
+        let grid = load_grid("test_input.txt").unwrap();
+        let antennas = find_antennas(&grid);
+        let report = ResonanceModel::new(vec![Harmonic::Multiple(2)]).analyse(&Bounds::of_grid(&grid), &antennas).unwrap();
+        // the puzzle's example, where one antinode sits on top of an A antenna
+        let expected = "\
+......#....#
+...#....0...
+....#0....#.
+..#....0....
+....0....#..
+.#....A.....
+...#........
+#......#....
+........A...
+.........A..
+..........#.
+..........#.
+";
+        assert_eq!(render_map(&grid, &report, &RenderOptions::default()), expected);
+        let options = RenderOptions { frequency: Some('A'), ..Default::default() };
+        let expected = "\
+............
+...#........
+....#.......
+............
+............
+......A.....
+............
+.......#....
+........A...
+.........A..
+..........#.
+..........#.
+";
+        assert_eq!(render_map(&grid, &report, &options), expected);
+    }
+
+    #[test]
+    fn test_render_skips_antinodes_off_the_grid() {
+        let mut grid = Grid::new(3, 3);
+        grid.fill('.');
+        *grid.get_mut(0, 0).unwrap() = 'a';
+        *grid.get_mut(1, 1).unwrap() = 'a';
+        let report = ResonanceModel::new(vec![Harmonic::Multiple(2)]).analyse(&Bounds::Plane, &find_antennas(&grid)).unwrap();
+        assert_eq!(report.all_antinodes().len(), 2);
+        let options = RenderOptions { lines: true, ..Default::default() };
+        assert_eq!(render_map(&grid, &report, &options), "a..\n.a.\n..#\n");
+    }
+
+    #[test]
+    fn test_render_lines_and_colour() {
+        let mut grid = Grid::new(3, 6);
+        grid.fill('.');
+        *grid.get_mut(1, 2).unwrap() = 'a';
+        *grid.get_mut(1, 3).unwrap() = 'a';
+        let antennas = find_antennas(&grid);
+        let report = ResonanceModel::new(vec![Harmonic::Multiple(2)]).analyse(&Bounds::of_grid(&grid), &antennas).unwrap();
+        let options = RenderOptions { lines: true, ..Default::default() };
+        assert_eq!(render_map(&grid, &report, &options), "......\n-#aa#-\n......\n");
+        let options = RenderOptions { colour: true, ..Default::default() };
+        assert_eq!(render_map(&grid, &report, &options).lines().nth(1).unwrap(),
+                   ".\x1b[31m#\x1b[0m\x1b[31ma\x1b[0m\x1b[31ma\x1b[0m\x1b[31m#\x1b[0m.");
+    }
+
+    #[test]
+    fn test_render_png() {
+        let grid = load_grid("test_input.txt").unwrap();
+        let antennas = find_antennas(&grid);
+        let report = ResonanceModel::new(vec![Harmonic::Multiple(2)]).analyse(&Bounds::of_grid(&grid), &antennas).unwrap();
+        let path = std::env::temp_dir().join(format!("day8_render_{}.png", std::process::id()));
+        render_png(&grid, &report, &RenderOptions::default(), 4, path.to_str().unwrap()).unwrap();
+        let img = image::open(&path).unwrap().to_rgba8();
+        assert_eq!(img.dimensions(), (48, 48));
+        // '0' is the first frequency, so the antenna at (1, 8) is red
+        assert_eq!(*img.get_pixel(8 * 4 + 1, 4 + 1), Rgba([220, 50, 47, 255]));
+        assert_eq!(*img.get_pixel(0, 0), Rgba([0, 0, 0, 255]));
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -1,11 +1,51 @@
 use std::cmp::Reverse;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 
 fn main() {
     let input_line = fs::read_to_string("input.txt").unwrap();
     let input = input_line.trim();
-    let file_blocks = expand_file_map(&input);
+    if !std::env::args().any(|arg| arg == "--expanded") {
+        let disk = parse_disk_map(input);
+        println!("The checksum is: {}", span_checksum(&compact_blocks_spans(&disk)));
+        println!(
+            "The checksum for compacting whole files is: {}",
+            span_checksum(&compact_whole_files_spans(&disk))
+        );
+        // --log=strategy prints each move and the disk after it, then --undo reverts them
+        if let Some(name) = std::env::args().find_map(|arg| arg.strip_prefix("--log=").map(str::to_string)) {
+            let strategy = BUILT_IN_STRATEGIES.iter().find(|strategy| strategy.name() == name).expect("Unknown strategy");
+            let (_, moves) = strategy.compact_logged(&disk);
+            let steps = render_steps(&disk, &moves);
+            println!("{}", steps[0]);
+            for (step, rendered) in moves.iter().zip(&steps[1..]) {
+                println!("file {} {:?} -> {:?} ({} blocks)", step.file_id, step.from, step.to, step.bytes());
+                println!("{}", rendered);
+            }
+            let mut replayer = Replayer::new(&disk, moves);
+            while replayer.step_forward().is_some() {}
+            println!("Checksum after replay: {}", span_checksum(&replayer.pieces()));
+            if std::env::args().any(|arg| arg == "--undo") {
+                while let Some(step) = replayer.step_back() {
+                    println!("undo file {} {:?} -> {:?}", step.file_id, step.to, step.from);
+                    println!("{}", replayer.render());
+                }
+            }
+        }
+        if std::env::args().any(|arg| arg == "--compare") {
+            println!("{:<16} {:>16} {:>10} {:>10} {:>10} {:>8} {:>8}", "strategy", "checksum", "gaps", "largest", "extent", "split", "frag");
+            for report in compare_strategies(&disk, BUILT_IN_STRATEGIES) {
+                let stats = report.stats;
+                println!(
+                    "{:<16} {:>16} {:>10} {:>10} {:>10} {:>8} {:>8.3}",
+                    report.strategy, report.checksum, stats.free_spans, stats.largest_free_span, stats.used_extent, stats.split_files,
+                    stats.external_fragmentation()
+                );
+            }
+        }
+        return;
+    }
+    let file_blocks = expand_file_map(input);
     let compacted_blocks = compact_single_file_blocks(&file_blocks);
     let checksum = compute_checksum(&compacted_blocks);
     println!("The checksum is: {}", checksum);
@@ -161,6 +201,449 @@ fn compute_checksum(blocks: &[i32]) -> i64 {
     checksum
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Span {
+    start: u64,
+    len: u64,
+}
+
+impl Span {
+    fn end(&self) -> u64 {
+        self.start + self.len
+    }
+}
+
+// The disk as spans rather than blocks, with files indexed by their ID
+#[derive(Debug, Clone, PartialEq)]
+struct DiskMap {
+    files: Vec<Span>,
+    free: Vec<Span>,
+}
+
+// A file ID and the blocks of it in one span
+type FilePiece = (usize, Span);
+
+// Blocks of one file moved from one span to another of the same length
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Move {
+    file_id: usize,
+    from: Span,
+    to: Span,
+}
+
+impl Move {
+    fn bytes(&self) -> u64 {
+        self.from.len
+    }
+}
+
+// The final layout, and the moves that got there in order
+type Compaction = (Vec<FilePiece>, Vec<Move>);
+
+// The puzzle's dense format has one digit per span. Lengths of more than one digit
+// can be given as numbers separated by whitespace or commas instead.
+fn parse_disk_map(input: &str) -> DiskMap {
+    let lengths: Vec<u64> = if input.contains(|c: char| c.is_whitespace() || c == ',') {
+        input
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|length| !length.is_empty())
+            .map(|length| length.parse().unwrap())
+            .collect()
+    } else {
+        input.chars().map(|char| char.to_digit(10).unwrap() as u64).collect()
+    };
+    let mut disk = DiskMap { files: Vec::new(), free: Vec::new() };
+    let mut position = 0;
+    for (i, &len) in lengths.iter().enumerate() {
+        let span = Span { start: position, len };
+        if i % 2 == 0 {
+            disk.files.push(span);
+        } else if len > 0 {
+            disk.free.push(span);
+        }
+        position += len;
+    }
+    disk
+}
+
+// Moves blocks one at a time from the end of the disk into the leftmost free block,
+// like compact_single_file_blocks, but a run of blocks at a time
+fn compact_blocks_spans(disk: &DiskMap) -> Vec<FilePiece> {
+    compact_blocks_logged(disk).0
+}
+
+fn compact_blocks_logged(disk: &DiskMap) -> Compaction {
+    let mut remaining = disk.files.clone();
+    let mut moved = Vec::new();
+    let mut moves = Vec::new();
+    let mut file_id = remaining.len();
+    'free: for free in &disk.free {
+        let mut free = *free;
+        while free.len > 0 {
+            while file_id > 0 && remaining[file_id - 1].len == 0 {
+                file_id -= 1;
+            }
+            if file_id == 0 || remaining[file_id - 1].start <= free.start {
+                break 'free;
+            }
+            let file = &mut remaining[file_id - 1];
+            let count = free.len.min(file.len);
+            let to = Span { start: free.start, len: count };
+            moves.push(Move { file_id: file_id - 1, from: Span { start: file.end() - count, len: count }, to });
+            moved.push((file_id - 1, to));
+            file.len -= count;
+            free = Span { start: free.start + count, len: free.len - count };
+        }
+    }
+    let pieces = remaining.into_iter().enumerate().filter(|(_, span)| span.len > 0).chain(moved).collect();
+    (pieces, moves)
+}
+
+// Max length over ranges of free spans, kept in disk order, so the leftmost span
+// that a file fits in can be found in O(log n)
+struct FreeSpaceTree {
+    leaves: usize,
+    max: Vec<u64>,
+    spans: Vec<Span>,
+}
+
+impl FreeSpaceTree {
+    fn new(spans: Vec<Span>) -> FreeSpaceTree {
+        let leaves = spans.len().next_power_of_two();
+        let mut max = vec![0; 2 * leaves];
+        for (i, span) in spans.iter().enumerate() {
+            max[leaves + i] = span.len;
+        }
+        for node in (1..leaves).rev() {
+            max[node] = max[2 * node].max(max[2 * node + 1]);
+        }
+        FreeSpaceTree { leaves, max, spans }
+    }
+
+    fn leftmost_fitting(&self, len: u64) -> Option<usize> {
+        if self.max[1] < len {
+            return None;
+        }
+        let mut node = 1;
+        while node < self.leaves {
+            node = if self.max[2 * node] >= len { 2 * node } else { 2 * node + 1 };
+        }
+        // an empty file "fits" the padding leaves too, which aren't real spans
+        Some(node - self.leaves).filter(|&idx| idx < self.spans.len())
+    }
+
+    // Uses `len` blocks from the front of a free span, returning where they start
+    fn take(&mut self, idx: usize, len: u64) -> u64 {
+        let span = &mut self.spans[idx];
+        let start = span.start;
+        *span = Span { start: start + len, len: span.len - len };
+        let mut node = self.leaves + idx;
+        self.max[node] = span.len;
+        while node > 1 {
+            node /= 2;
+            self.max[node] = self.max[2 * node].max(self.max[2 * node + 1]);
+        }
+        start
+    }
+}
+
+// Each file, highest ID first, moves once to the leftmost free span it fits in, like
+// compact_whole_files. Space a file leaves behind is always right of the files still
+// to move, so it never needs to go back into the tree.
+fn compact_whole_files_spans(disk: &DiskMap) -> Vec<FilePiece> {
+    compact_whole_files_logged(disk).0
+}
+
+fn compact_whole_files_logged(disk: &DiskMap) -> Compaction {
+    let mut free = FreeSpaceTree::new(disk.free.clone());
+    let mut files: Vec<FilePiece> = disk.files.iter().copied().enumerate().collect();
+    let mut moves = Vec::new();
+    for (file_id, file) in files.iter_mut().rev() {
+        if let Some(idx) = free.leftmost_fitting(file.len) {
+            if free.spans[idx].start < file.start {
+                let from = *file;
+                file.start = free.take(idx, file.len);
+                if file.len > 0 {
+                    moves.push(Move { file_id: *file_id, from, to: *file });
+                }
+            }
+        }
+    }
+    (files, moves)
+}
+
+fn span_checksum(pieces: &[FilePiece]) -> u128 {
+    pieces
+        .iter()
+        .map(|&(file_id, span)| {
+            // file_id * (start + start+1 + ... + end-1)
+            let (start, end) = (span.start as u128, span.end() as u128);
+            file_id as u128 * (end * (end.saturating_sub(1)) - start * start.saturating_sub(1)) / 2
+        })
+        .sum()
+}
+
+/// A policy for rearranging the files on a disk
+trait CompactionStrategy {
+    fn name(&self) -> &str;
+    fn compact_logged(&self, disk: &DiskMap) -> Compaction;
+
+    fn compact(&self, disk: &DiskMap) -> Vec<FilePiece> {
+        self.compact_logged(disk).0
+    }
+}
+
+/// Single blocks from the end into the leftmost free block (part 1)
+struct BlockByBlock;
+/// Whole files into the leftmost span they fit in (part 2)
+struct FirstFit;
+/// Whole files into the smallest span they fit in
+struct BestFit;
+/// Whole files into the largest span left of them, if they fit
+struct WorstFit;
+/// Whole files into the first span they fit in, searching on from the last span used
+struct NextFit;
+/// Every file slid left, in disk order, so there are no gaps at all
+struct Defragment;
+
+const BUILT_IN_STRATEGIES: &[&dyn CompactionStrategy] =
+    &[&BlockByBlock, &FirstFit, &BestFit, &WorstFit, &NextFit, &Defragment];
+
+impl CompactionStrategy for BlockByBlock {
+    fn name(&self) -> &str { "block-by-block" }
+    fn compact_logged(&self, disk: &DiskMap) -> Compaction { compact_blocks_logged(disk) }
+}
+
+impl CompactionStrategy for FirstFit {
+    fn name(&self) -> &str { "first-fit" }
+    fn compact_logged(&self, disk: &DiskMap) -> Compaction { compact_whole_files_logged(disk) }
+}
+
+impl CompactionStrategy for BestFit {
+    fn name(&self) -> &str { "best-fit" }
+    fn compact_logged(&self, disk: &DiskMap) -> Compaction {
+        move_whole_files(disk, |free, file| {
+            free.iter()
+                .enumerate()
+                .take_while(|(_, space)| space.start < file.start)
+                .filter(|(_, space)| space.len >= file.len)
+                .min_by_key(|&(i, space)| (space.len, i))
+                .map(|(i, _)| i)
+        })
+    }
+}
+
+impl CompactionStrategy for WorstFit {
+    fn name(&self) -> &str { "worst-fit" }
+    fn compact_logged(&self, disk: &DiskMap) -> Compaction {
+        move_whole_files(disk, |free, file| {
+            free.iter()
+                .enumerate()
+                .take_while(|(_, space)| space.start < file.start)
+                .max_by_key(|&(i, space)| (space.len, Reverse(i)))
+                .filter(|(_, space)| space.len >= file.len)
+                .map(|(i, _)| i)
+        })
+    }
+}
+
+impl CompactionStrategy for NextFit {
+    fn name(&self) -> &str { "next-fit" }
+    fn compact_logged(&self, disk: &DiskMap) -> Compaction {
+        let mut cursor = 0;
+        move_whole_files(disk, |free, file| {
+            let found = (cursor..free.len())
+                .chain(0..cursor)
+                .find(|&i| free[i].start < file.start && free[i].len >= file.len)?;
+            cursor = found;
+            Some(found)
+        })
+    }
+}
+
+impl CompactionStrategy for Defragment {
+    fn name(&self) -> &str { "defragment" }
+    fn compact_logged(&self, disk: &DiskMap) -> Compaction {
+        let mut files: Vec<FilePiece> = disk.files.iter().copied().enumerate().collect();
+        files.sort_by_key(|(_, span)| span.start);
+        let mut position = 0;
+        let mut moves = Vec::new();
+        for (file_id, span) in files.iter_mut() {
+            if span.start != position && span.len > 0 {
+                moves.push(Move { file_id: *file_id, from: *span, to: Span { start: position, len: span.len } });
+            }
+            span.start = position;
+            position += span.len;
+        }
+        files.sort_by_key(|&(file_id, _)| file_id);
+        (files, moves)
+    }
+}
+
+// Each file, highest ID first, moves once to the free span `choose` picks for it, if any.
+// Only spans left of the file are used, as with compact_whole_files.
+fn move_whole_files(disk: &DiskMap, mut choose: impl FnMut(&[Span], Span) -> Option<usize>) -> Compaction {
+    let mut free = disk.free.clone();
+    let mut files: Vec<FilePiece> = disk.files.iter().copied().enumerate().collect();
+    let mut moves = Vec::new();
+    for (file_id, file) in files.iter_mut().rev() {
+        if let Some(idx) = choose(&free, *file).filter(|&idx| free[idx].start < file.start) {
+            let space = &mut free[idx];
+            let from = *file;
+            file.start = space.start;
+            *space = Span { start: space.start + file.len, len: space.len - file.len };
+            if file.len > 0 {
+                moves.push(Move { file_id: *file_id, from, to: *file });
+            }
+        }
+    }
+    (files, moves)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct FragmentationStats {
+    // gaps before the last used block
+    free_spans: usize,
+    free_blocks: u64,
+    largest_free_span: u64,
+    // one past the last used block
+    used_extent: u64,
+    // files stored in more than one piece
+    split_files: usize,
+}
+
+impl FragmentationStats {
+    fn of(pieces: &[FilePiece]) -> FragmentationStats {
+        let mut pieces: Vec<FilePiece> = pieces.iter().copied().filter(|(_, span)| span.len > 0).collect();
+        pieces.sort_by_key(|(_, span)| span.start);
+        let mut stats = FragmentationStats { free_spans: 0, free_blocks: 0, largest_free_span: 0, used_extent: 0, split_files: 0 };
+        for (_, span) in &pieces {
+            if span.start > stats.used_extent {
+                let gap = span.start - stats.used_extent;
+                stats.free_spans += 1;
+                stats.free_blocks += gap;
+                stats.largest_free_span = stats.largest_free_span.max(gap);
+            }
+            stats.used_extent = stats.used_extent.max(span.end());
+        }
+        // pieces of the same file that touch count as one
+        let mut runs: HashMap<usize, usize> = HashMap::new();
+        let mut previous: Option<FilePiece> = None;
+        for &(file_id, span) in &pieces {
+            if previous.is_none_or(|(id, prev)| id != file_id || prev.end() != span.start) {
+                *runs.entry(file_id).or_default() += 1;
+            }
+            previous = Some((file_id, span));
+        }
+        stats.split_files = runs.values().filter(|&&count| count > 1).count();
+        stats
+    }
+
+    // 0 when the free space is all in one gap, approaching 1 as it is split into many
+    fn external_fragmentation(&self) -> f64 {
+        if self.free_blocks == 0 {
+            0.0
+        } else {
+            1.0 - self.largest_free_span as f64 / self.free_blocks as f64
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct CompactionReport {
+    strategy: String,
+    checksum: u128,
+    stats: FragmentationStats,
+}
+
+fn compare_strategies(disk: &DiskMap, strategies: &[&dyn CompactionStrategy]) -> Vec<CompactionReport> {
+    strategies
+        .iter()
+        .map(|strategy| {
+            let pieces = strategy.compact(disk);
+            CompactionReport {
+                strategy: strategy.name().to_string(),
+                checksum: span_checksum(&pieces),
+                stats: FragmentationStats::of(&pieces),
+            }
+        })
+        .collect()
+}
+
+// Steps through a move log, forwards or backwards, from the disk it was made on
+struct Replayer {
+    // pieces by start block
+    layout: BTreeMap<u64, FilePiece>,
+    disk_len: u64,
+    moves: Vec<Move>,
+    applied: usize,
+}
+
+impl Replayer {
+    fn new(disk: &DiskMap, moves: Vec<Move>) -> Replayer {
+        let layout = disk.files.iter()
+            .copied()
+            .enumerate()
+            .filter(|(_, span)| span.len > 0)
+            .map(|piece| (piece.1.start, piece))
+            .collect();
+        let disk_len = disk.files.iter().chain(&disk.free).map(Span::end).max().unwrap_or(0);
+        Replayer { layout, disk_len, moves, applied: 0 }
+    }
+
+    // Takes `from` out of whichever piece of the file holds it and puts it at `to`
+    fn relocate(&mut self, file_id: usize, from: Span, to: Span) {
+        let (&start, &(id, piece)) = self.layout.range(..=from.start).next_back().expect("No piece to move");
+        assert!(id == file_id && from.end() <= piece.end(), "Move doesn't match the layout");
+        self.layout.remove(&start);
+        if piece.start < from.start {
+            self.layout.insert(piece.start, (id, Span { start: piece.start, len: from.start - piece.start }));
+        }
+        if from.end() < piece.end() {
+            self.layout.insert(from.end(), (id, Span { start: from.end(), len: piece.end() - from.end() }));
+        }
+        self.layout.insert(to.start, (file_id, to));
+    }
+
+    fn step_forward(&mut self) -> Option<Move> {
+        let step = *self.moves.get(self.applied)?;
+        self.relocate(step.file_id, step.from, step.to);
+        self.applied += 1;
+        Some(step)
+    }
+
+    fn step_back(&mut self) -> Option<Move> {
+        self.applied = self.applied.checked_sub(1)?;
+        let step = self.moves[self.applied];
+        self.relocate(step.file_id, step.to, step.from);
+        Some(step)
+    }
+
+    fn pieces(&self) -> Vec<FilePiece> {
+        self.layout.values().copied().collect()
+    }
+
+    // In the puzzle's style, one character per block with the last digit of the file ID
+    fn render(&self) -> String {
+        let mut disk = vec!['.'; self.disk_len as usize];
+        for &(file_id, span) in self.layout.values() {
+            let digit = char::from_digit((file_id % 10) as u32, 10).unwrap();
+            disk[span.start as usize..span.end() as usize].fill(digit);
+        }
+        disk.into_iter().collect()
+    }
+}
+
+// The disk before any moves and after each one
+fn render_steps(disk: &DiskMap, moves: &[Move]) -> Vec<String> {
+    let mut replayer = Replayer::new(disk, moves.to_vec());
+    let mut steps = vec![replayer.render()];
+    while replayer.step_forward().is_some() {
+        steps.push(replayer.render());
+    }
+    steps
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -230,4 +713,189 @@ mod tests {
         assert_eq!(free_spaces.get(&1).unwrap().len(), 5 as usize);
         assert_eq!(free_spaces.len(), 2 as usize);
     }
+
+    // A deterministic jumble of digits, so the block and span compactors can be compared
+    fn scrambled_digits(len: usize) -> String {
+        let mut state: u64 = 12345;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                char::from_digit(((state >> 33) % 10) as u32, 10).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_disk_map() {
+        let disk = parse_disk_map("12345");
+        assert_eq!(disk.files, vec![Span { start: 0, len: 1 }, Span { start: 3, len: 3 }, Span { start: 10, len: 5 }]);
+        assert_eq!(disk.free, vec![Span { start: 1, len: 2 }, Span { start: 6, len: 4 }]);
+        assert_eq!(parse_disk_map("1 2 3 4 5"), disk);
+        let disk = parse_disk_map("12, 0, 30");
+        assert_eq!(disk.files, vec![Span { start: 0, len: 12 }, Span { start: 12, len: 30 }]);
+        assert!(disk.free.is_empty());
+    }
+
+    #[test]
+    fn test_span_compaction_checksums() {
+        let disk = parse_disk_map("2333133121414131402");
+        assert_eq!(span_checksum(&compact_blocks_spans(&disk)), 1928);
+        assert_eq!(span_checksum(&compact_whole_files_spans(&disk)), 2858);
+    }
+
+    #[test]
+    fn test_span_compaction_matches_blocks() {
+        let input = scrambled_digits(2001);
+        let blocks = expand_file_map(&input);
+        let disk = parse_disk_map(&input);
+        assert_eq!(
+            span_checksum(&compact_blocks_spans(&disk)),
+            compute_checksum(&compact_single_file_blocks(&blocks)) as u128
+        );
+        assert_eq!(
+            compact_whole_files_spans(&disk),
+            whole_files_reference(&disk)
+        );
+    }
+
+    // Straightforward scan of every free span for each file
+    fn whole_files_reference(disk: &DiskMap) -> Vec<FilePiece> {
+        let mut free = disk.free.clone();
+        let mut files: Vec<FilePiece> = disk.files.iter().copied().enumerate().collect();
+        for (_, file) in files.iter_mut().rev() {
+            if let Some(space) = free.iter_mut().take_while(|space| space.start < file.start).find(|space| space.len >= file.len) {
+                file.start = space.start;
+                *space = Span { start: space.start + file.len, len: space.len - file.len };
+            }
+        }
+        files
+    }
+
+    #[test]
+    fn test_span_compaction_without_free_space() {
+        // an empty file and nowhere to put it
+        let disk = parse_disk_map("100");
+        assert_eq!(compact_whole_files_spans(&disk), vec![(0, Span { start: 0, len: 1 }), (1, Span { start: 1, len: 0 })]);
+        assert_eq!(compare_strategies(&disk, BUILT_IN_STRATEGIES).len(), BUILT_IN_STRATEGIES.len());
+    }
+
+    #[test]
+    fn test_span_compaction_long_spans() {
+        // the 12 block file fits in the 15 block gap once 2 has moved into the front of it
+        let disk = parse_disk_map("1 15 3 1 12 1 2");
+        let files = compact_whole_files_spans(&disk);
+        assert_eq!(files, vec![
+            (0, Span { start: 0, len: 1 }),
+            (1, Span { start: 16, len: 3 }),
+            (2, Span { start: 3, len: 12 }),
+            (3, Span { start: 1, len: 2 }),
+        ]);
+    }
+
+    #[test]
+    fn test_span_compaction_large_disk() {
+        let input = scrambled_digits(2_000_001);
+        let disk = parse_disk_map(&input);
+        let files = compact_whole_files_spans(&disk);
+        assert_eq!(files.len(), 1_000_001);
+        let blocks = compact_blocks_spans(&disk);
+        let total = |pieces: &[FilePiece]| pieces.iter().map(|(_, span)| span.len).sum::<u64>();
+        assert_eq!(total(&blocks), total(&files));
+    }
+
+    #[test]
+    fn test_strategies_on_example() {
+        let disk = parse_disk_map("2333133121414131402");
+        let checksums: Vec<u128> = compare_strategies(&disk, BUILT_IN_STRATEGIES).iter().map(|report| report.checksum).collect();
+        assert_eq!(checksums, vec![1928, 2858, 2858, 2858, 2858, 2453]);
+    }
+
+    #[test]
+    fn test_strategies_differ() {
+        let disk = parse_disk_map("36641632213");
+        let reports = compare_strategies(&disk, &[&FirstFit, &BestFit, &WorstFit, &NextFit]);
+        let checksums: Vec<u128> = reports.iter().map(|report| report.checksum).collect();
+        assert_eq!(checksums, vec![341, 617, 449, 361]);
+        // best-fit: 000333...1111115552..........44
+        assert_eq!(reports[1].stats, FragmentationStats {
+            free_spans: 2,
+            free_blocks: 13,
+            largest_free_span: 10,
+            used_extent: 31,
+            split_files: 0,
+        });
+        assert_eq!(reports[0].stats.free_blocks, 0);
+    }
+
+    #[test]
+    fn test_defragment() {
+        let disk = parse_disk_map("36641632213");
+        let pieces = Defragment.compact(&disk);
+        let stats = FragmentationStats::of(&pieces);
+        assert_eq!(stats.free_spans, 0);
+        assert_eq!(stats.used_extent, disk.files.iter().map(|span| span.len).sum::<u64>());
+        assert_eq!(stats.external_fragmentation(), 0.0);
+    }
+
+    #[test]
+    fn test_fragmentation_stats() {
+        // block by block splits files 8 and 6: 0099811188827773336446555566..............
+        let pieces = BlockByBlock.compact(&parse_disk_map("2333133121414131402"));
+        let stats = FragmentationStats::of(&pieces);
+        assert_eq!(stats.split_files, 2);
+        assert_eq!(stats.used_extent, 28);
+        assert_eq!(stats.free_spans, 0);
+
+        let stats = FragmentationStats::of(&[(0, Span { start: 0, len: 1 }), (1, Span { start: 2, len: 1 }), (2, Span { start: 6, len: 1 })]);
+        assert_eq!((stats.free_spans, stats.free_blocks, stats.largest_free_span), (2, 4, 3));
+        assert_eq!(stats.external_fragmentation(), 0.25);
+    }
+
+    #[test]
+    fn test_move_log() {
+        let disk = parse_disk_map("2333133121414131402");
+        let (_, moves) = FirstFit.compact_logged(&disk);
+        assert_eq!(moves, vec![
+            Move { file_id: 9, from: Span { start: 40, len: 2 }, to: Span { start: 2, len: 2 } },
+            Move { file_id: 7, from: Span { start: 32, len: 3 }, to: Span { start: 8, len: 3 } },
+            Move { file_id: 4, from: Span { start: 19, len: 2 }, to: Span { start: 12, len: 2 } },
+            Move { file_id: 2, from: Span { start: 11, len: 1 }, to: Span { start: 4, len: 1 } },
+        ]);
+        assert_eq!(moves.iter().map(Move::bytes).sum::<u64>(), 8);
+    }
+
+    #[test]
+    fn test_render_steps() {
+        // the puzzle's walkthrough of moving whole files
+        let disk = parse_disk_map("2333133121414131402");
+        let (_, moves) = FirstFit.compact_logged(&disk);
+        assert_eq!(render_steps(&disk, &moves), vec![
+            "00...111...2...333.44.5555.6666.777.888899",
+            "0099.111...2...333.44.5555.6666.777.8888..",
+            "0099.1117772...333.44.5555.6666.....8888..",
+            "0099.111777244.333....5555.6666.....8888..",
+            "00992111777.44.333....5555.6666.....8888..",
+        ]);
+        let disk = parse_disk_map("12345");
+        let (_, moves) = BlockByBlock.compact_logged(&disk);
+        assert_eq!(render_steps(&disk, &moves), vec![
+            "0..111....22222",
+            "022111....222..",
+            "022111222......",
+        ]);
+    }
+
+    #[test]
+    fn test_replay_forward_and_back() {
+        let disk = parse_disk_map(&scrambled_digits(201));
+        for strategy in BUILT_IN_STRATEGIES {
+            let (pieces, moves) = strategy.compact_logged(&disk);
+            let mut replayer = Replayer::new(&disk, moves);
+            let start = replayer.render();
+            while replayer.step_forward().is_some() {}
+            assert_eq!(span_checksum(&replayer.pieces()), span_checksum(&pieces), "{}", strategy.name());
+            while replayer.step_back().is_some() {}
+            assert_eq!(replayer.render(), start, "{}", strategy.name());
+        }
+    }
 }